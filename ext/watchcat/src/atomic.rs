@@ -0,0 +1,283 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use notify::{
+    event::{DataChange, ModifyKind, RenameMode},
+    Event, EventKind,
+};
+
+use crate::filter;
+
+// How far apart the steps of a single save may be.
+const WINDOW: Duration = Duration::from_millis(100);
+
+// How long events are held at most, so that a file written to continuously
+// (a log, a download) is still reported.
+const MAX_DELAY: Duration = Duration::from_secs(1);
+
+// The files touched by one (suspected) save and the events held back for it.
+#[derive(Debug)]
+struct SaveGroup {
+    // Temporary and backup files the editor wrote or moved things to.
+    members: Vec<PathBuf>,
+    // The file being saved, once known.
+    dest: Option<PathBuf>,
+    events: Vec<Event>,
+    // When the first event was held.
+    since: Instant,
+    deadline: Instant,
+    // The save has been reported (or turned out to be a no-op); the group is
+    // only kept to swallow the editor's cleanup of its members.
+    done: bool,
+}
+
+impl SaveGroup {
+    fn new(member: Option<PathBuf>, dest: Option<PathBuf>, event: Event, now: Instant) -> Self {
+        Self {
+            members: member.into_iter().collect(),
+            dest,
+            events: vec![event],
+            since: now,
+            deadline: now + WINDOW,
+            done: false,
+        }
+    }
+
+    fn hold(&mut self, event: Event, now: Instant) {
+        self.events.push(event);
+        self.deadline = (now + WINDOW).min(self.since + MAX_DELAY);
+    }
+
+    fn finish(&mut self, now: Instant) {
+        self.events.clear();
+        self.done = true;
+        self.deadline = now + WINDOW;
+    }
+}
+
+/// Coalesces "write a temporary file, then rename it over the original"
+/// saves into a single modify event for the original.
+///
+/// Events for files created within the window are held back. If such a
+/// file is then renamed over another path, everything held for it is
+/// replaced by one `Modify(Data(Content))` for the destination. Moving the
+/// original to a backup name first (vim, JetBrains) and removing the backup
+/// afterwards are folded into the same save. Held events that turn out not
+/// to be part of a save are released unchanged once the window passes, or as
+/// soon as the file they're for is removed.
+#[derive(Debug, Default)]
+pub struct AtomicSaves {
+    groups: Vec<SaveGroup>,
+    // A `Name(From)` waiting for the `Name(To)` that completes it, for
+    // backends that report the two halves of a rename separately.
+    from: Option<(Event, Instant)>,
+}
+
+impl AtomicSaves {
+    pub fn process(&mut self, event: Event, now: Instant) -> Vec<Event> {
+        let mut ready = self.expire(now);
+
+        if let Some((from, _)) = self.from.take() {
+            if completes_rename(&from, &event) {
+                let (src, dst) = (from.paths[0].clone(), event.paths[0].clone());
+                if self.is_save_step(&src, &dst) {
+                    let mut both = Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::Both)))
+                        .add_path(src)
+                        .add_path(dst);
+                    both.attrs = event.attrs.clone();
+                    ready.extend(self.handle(both, now));
+                } else {
+                    ready.extend(self.handle(from, now));
+                    ready.extend(self.handle(event, now));
+                }
+                return ready;
+            }
+            ready.extend(self.handle(from, now));
+        }
+
+        if starts_rename(&event) {
+            self.from = Some((event, now));
+            return ready;
+        }
+
+        ready.extend(self.handle(event, now));
+        ready
+    }
+
+    /// Releases whatever has been held for longer than the window.
+    pub fn expire(&mut self, now: Instant) -> Vec<Event> {
+        let mut ready = Vec::new();
+
+        if self.from.as_ref().is_some_and(|(_, at)| *at + WINDOW <= now) {
+            let (from, _) = self.from.take().unwrap();
+            ready.extend(self.handle(from, now));
+        }
+
+        let (expired, kept): (Vec<_>, Vec<_>) = self.groups.drain(..).partition(|g| g.deadline <= now);
+        self.groups = kept;
+        for group in expired {
+            if !group.done {
+                ready.extend(group.events);
+            }
+        }
+
+        ready
+    }
+
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.groups
+            .iter()
+            .map(|g| g.deadline)
+            .chain(self.from.as_ref().map(|(_, at)| *at + WINDOW))
+            .min()
+    }
+
+    fn handle(&mut self, event: Event, now: Instant) -> Vec<Event> {
+        match event.kind {
+            EventKind::Create(_) => {
+                let Some(path) = single_path(&event) else {
+                    return vec![event];
+                };
+                // The original coming back after being moved to a backup.
+                if let Some(group) = self.groups.iter_mut().find(|g| !g.done && g.dest.as_deref() == Some(path)) {
+                    group.hold(event, now);
+                    return Vec::new();
+                }
+                let path = path.to_path_buf();
+                self.groups.push(SaveGroup::new(Some(path), None, event, now));
+                Vec::new()
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if event.paths.len() == 2 => {
+                let (src, dst) = (event.paths[0].clone(), event.paths[1].clone());
+
+                // Already handled through its `From`/`To` halves.
+                if self.groups.iter().any(|g| g.done && g.members.contains(&src)) {
+                    return Vec::new();
+                }
+                if let Some(group) = self
+                    .groups
+                    .iter_mut()
+                    .find(|g| !g.done && g.members.contains(&dst) && g.dest.as_ref() == Some(&src))
+                {
+                    group.hold(event, now);
+                    return Vec::new();
+                }
+
+                if let Some(i) = self.groups.iter().position(|g| !g.done && g.members.contains(&src)) {
+                    // A temporary file renamed over the destination: the save is complete.
+                    for group in self.groups.iter_mut().filter(|g| !g.done && g.dest.as_ref() == Some(&dst)) {
+                        group.finish(now);
+                    }
+                    let group = &mut self.groups[i];
+                    group.dest = Some(dst.clone());
+                    group.finish(now);
+                    return vec![modify_event(dst, &event)];
+                }
+
+                if filter::is_editor_temp(&dst) {
+                    // The original moved out of the way to a backup name.
+                    self.groups.push(SaveGroup::new(Some(dst), Some(src), event, now));
+                    return Vec::new();
+                }
+
+                vec![event]
+            }
+            EventKind::Remove(_) => {
+                let Some(path) = single_path(&event) else {
+                    return vec![event];
+                };
+                // The file may have been created again since an earlier group
+                // for it was done.
+                let of = |g: &SaveGroup| g.members.iter().any(|m| m == path);
+                let Some(i) = self
+                    .groups
+                    .iter()
+                    .position(|g| !g.done && of(g))
+                    .or_else(|| self.groups.iter().position(of))
+                else {
+                    return vec![event];
+                };
+                let group = &mut self.groups[i];
+                if group.done {
+                    return Vec::new();
+                }
+
+                // A short-lived file that has nothing to do with a save, like a
+                // lock file: reported as it happened.
+                if group.dest.is_none() && !filter::is_editor_temp(path) {
+                    let mut events = self.groups.remove(i).events;
+                    events.push(event);
+                    return events;
+                }
+
+                // The backup removed after the original was rewritten (vim), or
+                // a temporary file that never became anything (vim's `4913`).
+                let dest = group.dest.clone().filter(|dest| dest.exists());
+                group.finish(now);
+                dest.map(|dest| vec![modify_event(dest, &event)]).unwrap_or_default()
+            }
+            _ => {
+                let Some(path) = single_path(&event) else {
+                    return vec![event];
+                };
+                let group = self.groups.iter_mut().find(|g| {
+                    g.members.iter().any(|m| m == path) || (!g.done && g.dest.as_deref() == Some(path))
+                });
+                match group {
+                    Some(group) => {
+                        if !group.done {
+                            group.hold(event, now);
+                        }
+                        Vec::new()
+                    }
+                    None => vec![event],
+                }
+            }
+        }
+    }
+
+    fn is_save_step(&self, src: &Path, dst: &Path) -> bool {
+        filter::is_editor_temp(dst)
+            || self.groups.iter().any(|g| g.members.iter().any(|m| m == src))
+    }
+}
+
+// The first half of a rename reported as two events: `From` on inotify and
+// Windows, and a `Name(Any)` for a path that is gone on FSEvents.
+fn starts_rename(event: &Event) -> bool {
+    let [path] = event.paths.as_slice() else {
+        return false;
+    };
+    match event.kind {
+        EventKind::Modify(ModifyKind::Name(RenameMode::From)) => true,
+        EventKind::Modify(ModifyKind::Name(RenameMode::Any)) => !path.exists(),
+        _ => false,
+    }
+}
+
+fn completes_rename(from: &Event, event: &Event) -> bool {
+    let [path] = event.paths.as_slice() else {
+        return false;
+    };
+    let second_half = match event.kind {
+        EventKind::Modify(ModifyKind::Name(RenameMode::To)) => true,
+        EventKind::Modify(ModifyKind::Name(RenameMode::Any)) => path.exists(),
+        _ => false,
+    };
+    second_half && from.attrs.tracker() == event.attrs.tracker()
+}
+
+fn single_path(event: &Event) -> Option<&Path> {
+    match event.paths.as_slice() {
+        [path] => Some(path),
+        _ => None,
+    }
+}
+
+fn modify_event(path: PathBuf, cause: &Event) -> Event {
+    let mut event = Event::new(EventKind::Modify(ModifyKind::Data(DataChange::Content))).add_path(path);
+    event.attrs = cause.attrs.clone();
+    event.set_info("atomic_save")
+}
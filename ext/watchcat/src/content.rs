@@ -0,0 +1,260 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use notify::{
+    event::{AccessKind, AccessMode, ModifyKind, RenameMode},
+    Event, EventKind,
+};
+use serde::Serialize;
+use similar::TextDiff;
+use walkdir::WalkDir;
+
+use crate::filter;
+
+// `track_content`: which files to keep the contents of, to tell what changed
+// in them.
+#[derive(Clone, Debug, Serialize)]
+pub struct TrackContent {
+    pub patterns: Vec<String>,
+    // Larger files aren't kept, and have no diff.
+    pub max_bytes: usize,
+    // How much is kept at most, over all files. The ones changed the longest
+    // ago are dropped first.
+    pub max_total_bytes: usize,
+}
+
+// What changed in a file, for `Watchcat::Event#diff`.
+#[derive(Debug)]
+pub struct ContentDiff {
+    pub unified: String,
+    // The changed lines of each hunk, before and after, as 1-based
+    // `[start, end)` ranges. A range is empty for lines only added (before)
+    // or only removed (after).
+    pub hunks: Vec<((usize, usize), (usize, usize))>,
+}
+
+// The current contents of the files matching `track_content`, kept up with
+// every event from the backend, and what they were at the last event
+// reported for them, to diff against.
+pub struct ContentTracker {
+    options: TrackContent,
+    // With when each was last read, to tell which to drop first.
+    contents: HashMap<PathBuf, (String, u64)>,
+    // For the files changed since the last event reported for them, their
+    // contents at that event.
+    baselines: HashMap<PathBuf, String>,
+    size: usize,
+    reads: u64,
+}
+
+impl ContentTracker {
+    pub fn new(options: TrackContent) -> Self {
+        Self {
+            options,
+            contents: HashMap::new(),
+            baselines: HashMap::new(),
+            size: 0,
+            reads: 0,
+        }
+    }
+
+    // Reads the matching files under a root that was just watched, so that
+    // the first change to each has something to be diffed against. Stops
+    // once `max_total_bytes` is used up.
+    pub fn scan(&mut self, root: &Path, max_depth: Option<usize>, follow_links: bool) {
+        let mut walker = WalkDir::new(root).follow_links(follow_links);
+        if let Some(max_depth) = max_depth {
+            walker = walker.max_depth(max_depth);
+        }
+        for entry in walker.into_iter().filter_map(Result::ok) {
+            if self.size >= self.options.max_total_bytes {
+                break;
+            }
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let relative = entry.path().strip_prefix(root).unwrap_or(entry.path());
+            if self.matches(relative) {
+                if let Some(text) = self.read(entry.path()) {
+                    self.insert(entry.into_path(), text);
+                }
+            }
+        }
+    }
+
+    pub fn forget(&mut self, root: &Path) {
+        let size = &mut self.size;
+        self.contents.retain(|path, (text, _)| {
+            let keep = !path.starts_with(root);
+            if !keep {
+                *size -= text.len();
+            }
+            keep
+        });
+        self.baselines.retain(|path, _| !path.starts_with(root));
+    }
+
+    // Keeps up with the files an event from the backend changed, whether or
+    // not it ends up reported. `relative` gives a path relative to its watch
+    // root, for the patterns.
+    pub fn update(&mut self, event: &Event, relative: impl Fn(&Path) -> Option<PathBuf>) {
+        let path = match event.kind {
+            EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+                for path in &event.paths {
+                    self.forget(path);
+                }
+                return;
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+                let [from, to] = event.paths.as_slice() else {
+                    return;
+                };
+                // Forgetting the old path first keeps the contents of a file
+                // renamed over another diffed against what it replaced, as
+                // in an editor's atomic save.
+                let moved = self.remove(from);
+                self.baselines.remove(from);
+                if !self.contents.contains_key(to) {
+                    if let Some(text) = moved {
+                        self.insert(to.clone(), text);
+                    }
+                }
+                to
+            }
+            kind if !may_change(kind) => return,
+            _ => match event.paths.first() {
+                Some(path) => path,
+                None => return,
+            },
+        };
+
+        if !relative(path).is_some_and(|relative| self.matches(&relative)) {
+            return;
+        }
+        let Some(text) = self.read(path) else {
+            self.forget(path);
+            return;
+        };
+        let Some(old) = self.insert(path.clone(), text) else {
+            return;
+        };
+        if self.contents.get(path).is_some_and(|(new, _)| *new != old) {
+            self.baselines.entry(path.clone()).or_insert(old);
+        }
+    }
+
+    // What changed in the file a reported event is about since the last
+    // event reported for it.
+    pub fn take(&mut self, event: &Event) -> Option<ContentDiff> {
+        let path = match event.kind {
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => event.paths.get(1)?,
+            // Nothing was there before.
+            EventKind::Create(_) => {
+                for path in &event.paths {
+                    self.baselines.remove(path);
+                }
+                return None;
+            }
+            EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(RenameMode::From)) => return None,
+            kind if !may_change(kind) => return None,
+            _ => event.paths.first()?,
+        };
+        let old = self.baselines.remove(path)?;
+        let (new, _) = self.contents.get(path)?;
+        (old != *new).then(|| diff(path, &old, new))
+    }
+
+    // For an event that isn't reported after all, so that what it changed
+    // isn't in the next diff.
+    pub fn discard(&mut self, event: &Event) {
+        for path in &event.paths {
+            self.baselines.remove(path);
+        }
+    }
+
+    // Like the `patterns` option, against the base name or the path relative
+    // to the watch root.
+    fn matches(&self, relative: &Path) -> bool {
+        let relative = relative.to_string_lossy();
+        self.options
+            .patterns
+            .iter()
+            .any(|pattern| filter::glob_matches(pattern, &relative))
+    }
+
+    // Only files that are text (UTF-8) and within `max_bytes` are kept.
+    fn read(&self, path: &Path) -> Option<String> {
+        let metadata = std::fs::metadata(path).ok()?;
+        if !metadata.is_file() || metadata.len() > self.options.max_bytes as u64 {
+            return None;
+        }
+        let bytes = std::fs::read(path).ok()?;
+        if bytes.len() > self.options.max_bytes {
+            return None;
+        }
+        String::from_utf8(bytes).ok()
+    }
+
+    // Keeps `text` as the contents of `path`, and returns what it replaces.
+    fn insert(&mut self, path: PathBuf, text: String) -> Option<String> {
+        self.reads += 1;
+        self.size += text.len();
+        let old = self.contents.insert(path, (text, self.reads)).map(|(old, _)| old);
+        if let Some(old) = &old {
+            self.size -= old.len();
+        }
+        self.evict();
+        old
+    }
+
+    fn remove(&mut self, path: &Path) -> Option<String> {
+        let (text, _) = self.contents.remove(path)?;
+        self.size -= text.len();
+        Some(text)
+    }
+
+    // Drops the files read the longest ago until the rest fit in
+    // `max_total_bytes`.
+    fn evict(&mut self) {
+        while self.size > self.options.max_total_bytes {
+            let Some(oldest) = self
+                .contents
+                .iter()
+                .min_by_key(|(_, (_, read))| *read)
+                .map(|(path, _)| path.clone())
+            else {
+                break;
+            };
+            self.remove(&oldest);
+            self.baselines.remove(&oldest);
+        }
+    }
+}
+
+// Whether an event of `kind` may have changed the contents of its path.
+fn may_change(kind: EventKind) -> bool {
+    match kind {
+        EventKind::Modify(ModifyKind::Metadata(_)) | EventKind::Access(AccessKind::Open(_) | AccessKind::Read) => false,
+        EventKind::Access(AccessKind::Close(mode)) => mode == AccessMode::Write,
+        _ => true,
+    }
+}
+
+fn diff(path: &Path, old: &str, new: &str) -> ContentDiff {
+    let diff = TextDiff::from_lines(old, new);
+    let name = path.to_string_lossy();
+    let unified = diff.unified_diff().context_radius(3).header(&name, &name).to_string();
+    let hunks = diff
+        .grouped_ops(0)
+        .iter()
+        .filter_map(|group| {
+            let (first, last) = (group.first()?, group.last()?);
+            let old = (first.old_range().start + 1, last.old_range().end + 1);
+            let new = (first.new_range().start + 1, last.new_range().end + 1);
+            Some((old, new))
+        })
+        .collect();
+    ContentDiff { unified, hunks }
+}
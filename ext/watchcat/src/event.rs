@@ -1,14 +1,136 @@
+use std::{path::Path, time::SystemTime};
+
 use notify::{
     event::{
-        AccessKind, AccessMode, CreateKind, DataChange, MetadataKind, ModifyKind, RemoveKind,
-        RenameMode,
+        AccessKind, AccessMode, CreateKind, DataChange, Flag, MetadataKind, ModifyKind,
+        RemoveKind, RenameMode,
     },
-    EventKind,
+    Event, EventKind,
 };
 
+use crate::content::ContentDiff;
+
 #[derive(Debug)]
 pub enum WatchatEvent {}
 
+// An event as handed to Ruby.
+#[derive(Debug)]
+pub struct EventPayload {
+    pub kinds: Vec<String>,
+    pub paths: Vec<String>,
+    pub raw_kind: String,
+    pub details: EventDetails,
+}
+
+// Information resolved on the Rust side that doesn't fit into the kind
+// hierarchy. Handed to Ruby as keyword arguments for `Watchcat::Event.new`.
+#[derive(Debug, Default)]
+pub struct EventDetails {
+    pub root: Option<String>,
+    pub relative_paths: Vec<Option<String>>,
+    pub resolved_paths: Vec<String>,
+    pub attributes: EventAttributes,
+    // The name of the process in `attributes.process_id`, if it still runs.
+    pub process_name: Option<String>,
+    // What `lstat` said about each path when the event was received, with
+    // `stat`. `None` for a path that was gone by then.
+    pub stats: Option<Vec<Option<FileStat>>>,
+    // What changed in the file, with `track_content`.
+    pub diff: Option<ContentDiff>,
+    // What was appended to the file `Watchcat.tail` follows, a chunk per
+    // file read.
+    pub appended: Option<Vec<Vec<u8>>>,
+    // The normalized kind and whether the path is a directory, in `normalize`
+    // mode.
+    pub normalized: Option<(String, bool)>,
+    // When the event was reported to Ruby, in seconds since the Unix epoch.
+    pub time: f64,
+}
+
+// notify's `EventAttributes`, flattened to what Ruby can take as is.
+#[derive(Debug, Default)]
+pub struct EventAttributes {
+    pub tracker: Option<usize>,
+    pub flag: Option<String>,
+    pub info: Option<String>,
+    pub source: Option<String>,
+    pub process_id: Option<u32>,
+}
+
+impl From<&Event> for EventAttributes {
+    fn from(event: &Event) -> Self {
+        Self {
+            tracker: event.attrs.tracker(),
+            flag: event.attrs.flag().map(|flag| match flag {
+                Flag::Rescan => "rescan".to_string(),
+            }),
+            info: event.attrs.info().map(str::to_string),
+            source: event.attrs.source().map(str::to_string),
+            process_id: event.attrs.process_id(),
+        }
+    }
+}
+
+// A path's metadata, for `Watchcat::Event#stat`. `mode` and `ino` are only
+// known on Unix.
+#[derive(Debug)]
+pub struct FileStat {
+    pub size: u64,
+    pub mtime: Option<f64>,
+    pub mode: Option<u32>,
+    pub ino: Option<u64>,
+    pub file_type: &'static str,
+}
+
+impl FileStat {
+    // Without following a symlink, like the events themselves.
+    pub fn of(path: &Path) -> Option<Self> {
+        let metadata = std::fs::symlink_metadata(path).ok()?;
+        let file_type = metadata.file_type();
+        let file_type = if file_type.is_symlink() {
+            "link"
+        } else if file_type.is_dir() {
+            "directory"
+        } else if file_type.is_file() {
+            "file"
+        } else {
+            "other"
+        };
+
+        #[cfg(unix)]
+        let (mode, ino) = {
+            use std::os::unix::fs::MetadataExt;
+            (Some(metadata.mode()), Some(metadata.ino()))
+        };
+        #[cfg(not(unix))]
+        let (mode, ino) = (None, None);
+
+        Some(Self {
+            size: metadata.len(),
+            mtime: metadata
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+                .map(|d| d.as_secs_f64()),
+            mode,
+            ino,
+            file_type,
+        })
+    }
+}
+
+// The command name of a running process (`comm`), where the platform tells.
+#[cfg(target_os = "linux")]
+pub fn process_name(pid: u32) -> Option<String> {
+    let comm = std::fs::read_to_string(format!("/proc/{pid}/comm")).ok()?;
+    Some(comm.trim_end_matches('\n').to_string())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn process_name(_pid: u32) -> Option<String> {
+    None
+}
+
 impl WatchatEvent {
     pub fn convert_kind(kind: &EventKind) -> Vec<String> {
         let mut kinds = Vec::new();
@@ -26,7 +148,8 @@ impl WatchatEvent {
             EventKind::Remove(remove_kind) => {
                 kinds.extend(Self::remove_event(remove_kind));
             }
-            _ => {}
+            EventKind::Any => kinds.push("any".to_string()),
+            EventKind::Other => kinds.push("other".to_string()),
         }
 
         kinds
@@ -0,0 +1,45 @@
+use std::path::PathBuf;
+
+use notify::{
+    event::{AccessKind, AccessMode, CreateKind, DataChange, MetadataKind, ModifyKind, RemoveKind, RenameMode},
+    Event, EventKind,
+};
+
+// The kinds `Watchcat::Testing.inject` takes, and the event each stands for.
+// Picked to look like what inotify reports for the same change.
+const KINDS: &[(&str, EventKind)] = &[
+    ("create", EventKind::Create(CreateKind::File)),
+    ("create_folder", EventKind::Create(CreateKind::Folder)),
+    ("modify", EventKind::Modify(ModifyKind::Data(DataChange::Any))),
+    ("metadata", EventKind::Modify(ModifyKind::Metadata(MetadataKind::Any))),
+    ("rename", EventKind::Modify(ModifyKind::Name(RenameMode::Both))),
+    ("rename_from", EventKind::Modify(ModifyKind::Name(RenameMode::From))),
+    ("rename_to", EventKind::Modify(ModifyKind::Name(RenameMode::To))),
+    ("remove", EventKind::Remove(RemoveKind::File)),
+    ("remove_folder", EventKind::Remove(RemoveKind::Folder)),
+    ("access", EventKind::Access(AccessKind::Close(AccessMode::Write))),
+    ("any", EventKind::Any),
+    ("other", EventKind::Other),
+];
+
+// Makes up the event for `Watchcat::Testing.inject`.
+pub fn event(kind: &str, paths: Vec<String>) -> Result<Event, String> {
+    let Some(&(_, event_kind)) = KINDS.iter().find(|(name, _)| *name == kind) else {
+        let names = KINDS.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(", ");
+        return Err(format!("unknown kind: {kind} (expected one of {names})"));
+    };
+
+    match event_kind {
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if paths.len() != 2 => {
+            return Err(format!("{kind} takes 2 paths (from and to), got {}", paths.len()));
+        }
+        EventKind::Any | EventKind::Other => {}
+        _ if paths.is_empty() => return Err(format!("{kind} takes a path")),
+        _ => {}
+    }
+
+    Ok(paths
+        .into_iter()
+        .map(PathBuf::from)
+        .fold(Event::new(event_kind), Event::add_path))
+}
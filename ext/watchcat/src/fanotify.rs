@@ -0,0 +1,314 @@
+// `backend: :fanotify`: inotify, except that writes to files come from
+// fanotify, which tells which process made them. fanotify needs
+// CAP_SYS_ADMIN, and only reports writes (and not creating, removing or
+// renaming) when marking whole mounts, which is why inotify is kept for the
+// rest.
+
+#[cfg(target_os = "linux")]
+pub use linux::FanotifyWatcher;
+#[cfg(not(target_os = "linux"))]
+pub use other::FanotifyWatcher;
+
+// Whether fanotify can be used here: on Linux, with CAP_SYS_ADMIN.
+#[cfg(target_os = "linux")]
+pub fn available() -> bool {
+    let fd = unsafe { libc::fanotify_init(libc::FAN_CLASS_NOTIF | libc::FAN_CLOEXEC, libc::O_RDONLY as u32) };
+    if fd < 0 {
+        return false;
+    }
+    unsafe { libc::close(fd) };
+    true
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn available() -> bool {
+    false
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::{
+        ffi::CString,
+        io, mem,
+        os::unix::ffi::OsStrExt,
+        path::{Path, PathBuf},
+        sync::{Arc, Mutex},
+        thread::JoinHandle,
+    };
+
+    use notify::{
+        event::{AccessKind, AccessMode, DataChange, Flag, ModifyKind},
+        Config, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+    };
+
+    use crate::logging::log;
+    use crate::signal::ReadySignal;
+
+    type Sender = crossbeam_channel::Sender<notify::Result<Event>>;
+
+    #[derive(Debug)]
+    struct Root {
+        // As given to `watch`, which events are reported under.
+        path: PathBuf,
+        // As fanotify reports paths.
+        canonical: PathBuf,
+        recursive: bool,
+    }
+
+    #[derive(Debug)]
+    pub struct FanotifyWatcher {
+        inotify: RecommendedWatcher,
+        fd: i32,
+        // The roots whose mounts are marked.
+        roots: Arc<Mutex<Vec<Root>>>,
+        stop: Arc<ReadySignal>,
+        reader: Option<JoinHandle<()>>,
+    }
+
+    impl FanotifyWatcher {
+        pub fn new(tx: Sender, config: Config) -> notify::Result<Self> {
+            let fd = unsafe {
+                libc::fanotify_init(
+                    libc::FAN_CLASS_NOTIF | libc::FAN_CLOEXEC,
+                    (libc::O_RDONLY | libc::O_LARGEFILE | libc::O_CLOEXEC) as u32,
+                )
+            };
+            if fd < 0 {
+                return Err(notify::Error::io(io::Error::last_os_error()));
+            }
+
+            let roots = Arc::new(Mutex::new(Vec::new()));
+            let inotify_tx = tx.clone();
+            let inotify_roots = roots.clone();
+            let inotify = RecommendedWatcher::new(
+                move |res: notify::Result<Event>| {
+                    let marked = |event: &Event| {
+                        let roots = inotify_roots.lock().unwrap_or_else(|e| e.into_inner());
+                        event.paths.iter().all(|path| covered(&roots, path))
+                    };
+                    if !res.as_ref().is_ok_and(|event| from_fanotify(event) && marked(event)) {
+                        let _ = inotify_tx.send(res);
+                    }
+                },
+                config,
+            )
+            .inspect_err(|_| unsafe {
+                libc::close(fd);
+            })?;
+
+            let stop = Arc::new(
+                ReadySignal::new()
+                    .inspect_err(|_| unsafe {
+                        libc::close(fd);
+                    })
+                    .map_err(notify::Error::io)?,
+            );
+            let reader = {
+                let roots = roots.clone();
+                let stop = stop.clone();
+                std::thread::spawn(move || read_events(fd, &stop, &roots, &tx))
+            };
+
+            Ok(Self {
+                inotify,
+                fd,
+                roots,
+                stop,
+                reader: Some(reader),
+            })
+        }
+
+        pub fn watch(&mut self, path: &Path, mode: RecursiveMode) -> notify::Result<()> {
+            self.inotify.watch(path, mode)?;
+
+            let c_path = CString::new(path.as_os_str().as_bytes())
+                .map_err(|e| notify::Error::generic(&e.to_string()))?;
+            let marked = unsafe {
+                libc::fanotify_mark(
+                    self.fd,
+                    libc::FAN_MARK_ADD | libc::FAN_MARK_MOUNT,
+                    libc::FAN_MODIFY | libc::FAN_CLOSE_WRITE,
+                    libc::AT_FDCWD,
+                    c_path.as_ptr(),
+                )
+            };
+            if marked < 0 {
+                // Some mounts refuse to be marked; writes under the path are
+                // then reported by inotify, without the process behind them.
+                let error = io::Error::last_os_error();
+                log!(Warn, "fanotify can't watch path, reporting writes without their process", path = path.display(), error = error);
+                return Ok(());
+            }
+
+            let root = Root {
+                path: path.to_path_buf(),
+                canonical: path.canonicalize().unwrap_or_else(|_| path.to_path_buf()),
+                recursive: mode == RecursiveMode::Recursive,
+            };
+            self.roots.lock().unwrap_or_else(|e| e.into_inner()).push(root);
+            Ok(())
+        }
+
+        // The mount stays marked, but its events are no longer reported.
+        pub fn unwatch(&mut self, path: &Path) -> notify::Result<()> {
+            self.roots
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .retain(|root| root.path != path);
+            self.inotify.unwatch(path)
+        }
+    }
+
+    impl Drop for FanotifyWatcher {
+        fn drop(&mut self) {
+            self.stop.notify();
+            if let Some(reader) = self.reader.take() {
+                let _ = reader.join();
+            }
+            unsafe { libc::close(self.fd) };
+        }
+    }
+
+    fn read_events(fd: i32, stop: &ReadySignal, roots: &Mutex<Vec<Root>>, tx: &Sender) {
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            let mut fds = [
+                libc::pollfd { fd, events: libc::POLLIN, revents: 0 },
+                libc::pollfd { fd: stop.fd(), events: libc::POLLIN, revents: 0 },
+            ];
+            if unsafe { libc::poll(fds.as_mut_ptr(), 2, -1) } < 0 {
+                if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                break;
+            }
+            if fds[1].revents != 0 {
+                break;
+            }
+
+            let len = unsafe { libc::read(fd, buf.as_mut_ptr().cast(), buf.len()) };
+            if len < 0 {
+                let error = io::Error::last_os_error();
+                if error.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                let _ = tx.send(Err(notify::Error::io(error)));
+                break;
+            }
+
+            let mut offset = 0;
+            let header = mem::size_of::<libc::fanotify_event_metadata>();
+            while offset + header <= len as usize {
+                let metadata = unsafe {
+                    std::ptr::read_unaligned(buf[offset..].as_ptr().cast::<libc::fanotify_event_metadata>())
+                };
+                if metadata.vers != libc::FANOTIFY_METADATA_VERSION || (metadata.event_len as usize) < header {
+                    log!(Error, "unexpected fanotify event", version = metadata.vers);
+                    return;
+                }
+                offset += metadata.event_len as usize;
+
+                for event in convert(&metadata, roots) {
+                    if tx.send(Ok(event)).is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    fn convert(metadata: &libc::fanotify_event_metadata, roots: &Mutex<Vec<Root>>) -> Vec<Event> {
+        if metadata.mask & libc::FAN_Q_OVERFLOW != 0 {
+            return vec![Event::new(EventKind::Other).set_flag(Flag::Rescan)];
+        }
+        if metadata.fd < 0 {
+            return Vec::new();
+        }
+
+        let target = std::fs::read_link(format!("/proc/self/fd/{}", metadata.fd));
+        unsafe { libc::close(metadata.fd) };
+        let Ok(target) = target else {
+            return Vec::new();
+        };
+        let target = match target.to_str().and_then(|t| t.strip_suffix(" (deleted)")) {
+            Some(t) => PathBuf::from(t),
+            None => target,
+        };
+
+        // The whole mount is marked, so most events are for paths that
+        // aren't watched.
+        let Some(path) = resolve(&roots.lock().unwrap_or_else(|e| e.into_inner()), &target) else {
+            return Vec::new();
+        };
+
+        let mut events = Vec::new();
+        if metadata.mask & libc::FAN_MODIFY != 0 {
+            events.push(EventKind::Modify(ModifyKind::Data(DataChange::Any)));
+        }
+        if metadata.mask & libc::FAN_CLOSE_WRITE != 0 {
+            events.push(EventKind::Access(AccessKind::Close(AccessMode::Write)));
+        }
+        events
+            .into_iter()
+            .map(|kind| {
+                Event::new(kind)
+                    .add_path(path.clone())
+                    .set_process_id(metadata.pid as u32)
+            })
+            .collect()
+    }
+
+    // Whether `path`, as inotify reports it, is under a root marked for
+    // fanotify.
+    fn covered(roots: &[Root], path: &Path) -> bool {
+        roots.iter().any(|root| {
+            path.strip_prefix(&root.path)
+                .is_ok_and(|relative| root.recursive || relative.components().count() <= 1)
+        })
+    }
+
+    // What fanotify reports instead of inotify, for the paths it covers.
+    fn from_fanotify(event: &Event) -> bool {
+        matches!(
+            event.kind,
+            EventKind::Modify(ModifyKind::Data(_)) | EventKind::Access(AccessKind::Close(AccessMode::Write))
+        )
+    }
+
+    // `target` as it would be reported under the root it's in, if any.
+    fn resolve(roots: &[Root], target: &Path) -> Option<PathBuf> {
+        roots
+            .iter()
+            .filter_map(|root| {
+                let relative = target.strip_prefix(&root.canonical).ok()?;
+                let depth = relative.components().count();
+                (depth == 0 || root.recursive || depth == 1).then(|| (depth, root.path.join(relative)))
+            })
+            .min_by_key(|(depth, _)| *depth)
+            .map(|(_, path)| path)
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod other {
+    use std::path::Path;
+
+    use notify::{Config, Event, RecursiveMode};
+
+    #[derive(Debug)]
+    pub enum FanotifyWatcher {}
+
+    impl FanotifyWatcher {
+        pub fn new(_tx: crossbeam_channel::Sender<notify::Result<Event>>, _config: Config) -> notify::Result<Self> {
+            Err(notify::Error::generic("fanotify is only available on Linux"))
+        }
+
+        pub fn watch(&mut self, _path: &Path, _mode: RecursiveMode) -> notify::Result<()> {
+            match *self {}
+        }
+
+        pub fn unwatch(&mut self, _path: &Path) -> notify::Result<()> {
+            match *self {}
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use std::path::{Component, Path};
+
+/// Whether any component of `relative` (a path relative to its watch root)
+/// starts with a `.`. The root itself is never considered, so watching e.g.
+/// `~/.config` still reports its contents.
+pub fn is_hidden(relative: &Path) -> bool {
+    relative.components().any(|c| match c {
+        Component::Normal(name) => name.to_string_lossy().starts_with('.'),
+        _ => false,
+    })
+}
+
+/// Whether `path` looks like a temporary file written by an editor: vim swap
+/// files (and the `4913` file vim uses to probe a directory), emacs lock and
+/// auto-save files, JetBrains safe-write files and `~` backups.
+pub fn is_editor_temp(path: &Path) -> bool {
+    let Some(name) = path.file_name() else {
+        return false;
+    };
+    let name = name.to_string_lossy();
+
+    // vim
+    if name == "4913" || (name.starts_with('.') && [".swp", ".swo", ".swx"].iter().any(|ext| name.ends_with(ext))) {
+        return true;
+    }
+    // emacs
+    if name.starts_with(".#") || (name.len() > 1 && name.starts_with('#') && name.ends_with('#')) {
+        return true;
+    }
+    // JetBrains
+    if name.ends_with("___jb_tmp___") || name.ends_with("___jb_old___") {
+        return true;
+    }
+
+    name.ends_with('~')
+}
+
+/// Whether `name` matches the shell-style `pattern`: `*` matches any run of
+/// characters (`/` included), `?` any one character, `[...]` one of a set
+/// (`[!...]` or `[^...]` to negate, `a-z` for ranges), and `\` escapes the
+/// next character.
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    let (mut p, mut n) = (0, 0);
+    // Where to resume after the last `*` if the rest fails to match.
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        let next = match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+                continue;
+            }
+            Some('?') => Some(p + 1),
+            Some('[') => match_set(&pattern, p, name[n]),
+            Some('\\') if p + 1 < pattern.len() => (pattern[p + 1] == name[n]).then_some(p + 2),
+            Some(&c) => (c == name[n]).then_some(p + 1),
+            None => None,
+        };
+
+        match (next, star) {
+            (Some(next), _) => {
+                p = next;
+                n += 1;
+            }
+            (None, Some((star_p, star_n))) => {
+                star = Some((star_p, star_n + 1));
+                p = star_p + 1;
+                n = star_n + 1;
+            }
+            (None, None) => return false,
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Like the `patterns` option: the pattern can match the base name or the
+/// whole (relative) path.
+pub fn glob_matches(pattern: &str, relative: &str) -> bool {
+    let name = Path::new(relative).file_name().map(|n| n.to_string_lossy());
+    name.is_some_and(|name| glob_match(pattern, &name)) || glob_match(pattern, relative)
+}
+
+// Matches `c` against the set starting at `pattern[start]` (a `[`), returning
+// where the pattern continues if it matches. A `[` without a closing `]` is
+// taken literally.
+fn match_set(pattern: &[char], start: usize, c: char) -> Option<usize> {
+    let mut i = start + 1;
+    let negated = matches!(pattern.get(i), Some('!' | '^'));
+    if negated {
+        i += 1;
+    }
+
+    let mut matched = false;
+    let mut first = true;
+    while let Some(&lo) = pattern.get(i) {
+        if lo == ']' && !first {
+            return (matched != negated).then_some(i + 1);
+        }
+        first = false;
+
+        if pattern.get(i + 1) == Some(&'-') && pattern.get(i + 2).is_some_and(|&hi| hi != ']') {
+            matched |= (lo..=pattern[i + 2]).contains(&c);
+            i += 3;
+        } else {
+            matched |= lo == c;
+            i += 1;
+        }
+    }
+
+    (c == '[').then_some(start + 1)
+}
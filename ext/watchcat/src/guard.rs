@@ -0,0 +1,38 @@
+use std::{
+    any::Any,
+    panic::{catch_unwind, AssertUnwindSafe},
+};
+
+use magnus::{value::Lazy, Error, ExceptionClass, Module, Ruby};
+
+// Raised in place of a Rust panic, which would otherwise abort the process
+// when it unwinds into Ruby's C frames (or becomes a `fatal` when magnus
+// catches it).
+pub static INTERNAL_ERROR: Lazy<ExceptionClass> = Lazy::new(|ruby| {
+    ruby.define_module("Watchcat")
+        .and_then(|module| module.define_error("InternalError", ruby.exception_standard_error()))
+        .expect("failed to define Watchcat::InternalError")
+});
+
+// Runs the body of a method called from Ruby, turning a panic into a
+// `Watchcat::InternalError`. Must be called with the GVL held.
+pub fn guard<T>(f: impl FnOnce() -> Result<T, Error>) -> Result<T, Error> {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        let ruby = unsafe { Ruby::get_unchecked() };
+        Err(internal_error(&ruby, &panic_message(&*payload)))
+    })
+}
+
+pub fn internal_error(ruby: &Ruby, msg: &str) -> Error {
+    Error::new(ruby.get_inner(&INTERNAL_ERROR), format!("panic in watchcat: {msg}"))
+}
+
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg.to_string()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
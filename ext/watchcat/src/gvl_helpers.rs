@@ -1,11 +1,51 @@
-use std::{ffi::c_void, ptr::null_mut};
+use std::{
+    ffi::c_void,
+    panic::{catch_unwind, resume_unwind, AssertUnwindSafe},
+    ptr::null_mut,
+    thread,
+};
 
 use magnus::Ruby;
 use rb_sys::{
     rb_thread_call_with_gvl, rb_thread_call_without_gvl
 };
 
+// Both trampolines are called from C, where a panic must not unwind. They
+// catch it and hand it back, and it is resumed once we are out of the C call,
+// to be turned into a Ruby exception by `guard::guard`.
+
+// Ruby can't interrupt `f` (`Thread#kill`, `Thread#raise`, signals); they are
+// only handled once it returns.
 pub fn call_without_gvl<F, R>(f: F) -> R
+where
+    F: Send + FnOnce() -> R,
+{
+    without_gvl(f, None, null_mut())
+}
+
+// Like `call_without_gvl`, but Ruby calls `unblock` (from another thread)
+// when it wants to interrupt `f`, which must then return promptly. The
+// interrupt itself is only handled once back with the GVL, so the caller has
+// to check for it (`Ruby::thread_check_ints`).
+pub fn call_without_gvl_interruptible<F, U, R>(f: F, unblock: U) -> R
+where
+    F: Send + FnOnce() -> R,
+    U: Sync + Fn(),
+{
+    extern "C" fn unblock_trampoline<U>(arg: *mut c_void)
+    where
+        U: Fn(),
+    {
+        let unblock = unsafe { &*(arg as *const U) };
+        let _ = catch_unwind(AssertUnwindSafe(unblock));
+    }
+
+    without_gvl(f, Some(unblock_trampoline::<U>), &unblock as *const U as *mut c_void)
+}
+
+type UnblockFunction = Option<unsafe extern "C" fn(*mut c_void)>;
+
+fn without_gvl<F, R>(f: F, unblock: UnblockFunction, unblock_arg: *mut c_void) -> R
 where
     F: Send + FnOnce() -> R,
 {
@@ -14,9 +54,10 @@ where
         F: FnOnce() -> R,
     {
         let closure_ptr = arg as *mut Option<F>;
-        let closure = unsafe { (*closure_ptr).take().expect("Closure already taken") };
-
-        let result = closure();
+        let result: thread::Result<R> = catch_unwind(AssertUnwindSafe(|| {
+            let closure = unsafe { (*closure_ptr).take().expect("Closure already taken") };
+            closure()
+        }));
 
         let boxed_result = Box::new(result);
         Box::into_raw(boxed_result) as *mut c_void
@@ -29,13 +70,13 @@ where
         rb_thread_call_without_gvl(
             Some(trampoline::<F, R>),
             closure_ptr,
-            None,
-            null_mut(),
+            unblock,
+            unblock_arg,
         )
     };
 
-    let result_box = unsafe { Box::from_raw(raw_result_ptr as *mut R) };
-    *result_box
+    let result_box = unsafe { Box::from_raw(raw_result_ptr as *mut thread::Result<R>) };
+    result_box.unwrap_or_else(|payload| resume_unwind(payload))
 }
 
 pub fn call_with_gvl<F, R>(f: F) -> R
@@ -47,9 +88,10 @@ where
         F: FnOnce(Ruby) -> R,
     {
         let closure_ptr = arg as *mut Option<F>;
-        let closure = unsafe { (*closure_ptr).take().expect("Closure already taken") };
-
-        let result = closure(Ruby::get().unwrap());
+        let result: thread::Result<R> = catch_unwind(AssertUnwindSafe(|| {
+            let closure = unsafe { (*closure_ptr).take().expect("Closure already taken") };
+            closure(Ruby::get().unwrap())
+        }));
 
         let boxed_result = Box::new(result);
         Box::into_raw(boxed_result) as *mut c_void
@@ -60,6 +102,6 @@ where
 
     let raw_result_ptr = unsafe { rb_thread_call_with_gvl(Some(trampoline::<F, R>), closure_ptr) };
 
-    let result_box = unsafe { Box::from_raw(raw_result_ptr as *mut R) };
-    *result_box
+    let result_box = unsafe { Box::from_raw(raw_result_ptr as *mut thread::Result<R>) };
+    result_box.unwrap_or_else(|payload| resume_unwind(payload))
 }
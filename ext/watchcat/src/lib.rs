@@ -1,58 +1,222 @@
-use crossbeam_channel::{select, unbounded};
+use crossbeam_channel::{at, never, select, unbounded};
 use magnus::{
     function, method,
     scan_args::{get_kwargs, scan_args},
-    Error, Module, Object, Value, Ruby
+    value::Lazy,
+    Error, Module, Object, RHash, Value, Ruby
 };
-use notify::{Config, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher};
-use std::{path::Path, time::Duration, sync::{Arc, atomic::{AtomicBool, Ordering}}};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use std::sync::{Arc, OnceLock, atomic::{AtomicBool, Ordering}};
 
+mod atomic;
+mod content;
 mod event;
+mod fake;
+mod fanotify;
+mod filter;
+mod guard;
 mod gvl_helpers;
-use crate::event::WatchatEvent;
-use crate::gvl_helpers::{call_with_gvl, call_without_gvl};
-
-#[magnus::wrap(class = "Watchcat::Watcher")]
+mod logging;
+mod normalize;
+mod queue;
+mod record;
+mod roots;
+mod session;
+mod signal;
+mod symlinks;
+mod tail;
+mod wait;
+use crate::content::TrackContent;
+use crate::event::{EventDetails, EventPayload, FileStat};
+use crate::guard::{guard, internal_error, panic_message, INTERNAL_ERROR};
+use crate::gvl_helpers::{call_with_gvl, call_without_gvl, call_without_gvl_interruptible};
+use crate::logging::log;
+use crate::queue::{Drained, Ending, EventQueue};
+use crate::record::Entry;
+use crate::session::{Backend, Command, WatchOptions, WatchSession};
+use crate::wait::{WaitTarget, Waited, Waiter};
+
+// Holds nothing but channels, atomics and a thread-safe queue, all of which
+// are safe to use from any Ractor, so a frozen watcher can be shared between
+// Ractors.
+#[magnus::wrap(class = "Watchcat::Watcher", frozen_shareable)]
 struct WatchcatWatcher {
     tx: crossbeam_channel::Sender<bool>,
     rx: crossbeam_channel::Receiver<bool>,
     terminated: Arc<AtomicBool>,
     cmd_tx: crossbeam_channel::Sender<Command>,
     cmd_rx: crossbeam_channel::Receiver<Command>,
+    // Set by `start`, which runs the loop on a thread of its own.
+    queue: OnceLock<Arc<EventQueue>>,
 }
 
-#[derive(Debug)]
-enum WatcherEnum {
-    Poll(PollWatcher),
-    Recommended(RecommendedWatcher),
+// Carries a failure out of the GVL-released section without touching Ruby.
+// `magnus::Error` (and the `Ruby` handle needed to build one) must only be
+// used while the GVL is held, so the actual `magnus::Error` is constructed
+// after control returns from `call_without_gvl`.
+enum WatchFailure {
+    Runtime(String),
 }
 
-fn watcher_watch(w: &mut WatcherEnum, path: &Path, mode: RecursiveMode) -> notify::Result<()> {
-    match w {
-        WatcherEnum::Poll(x) => x.watch(path, mode),
-        WatcherEnum::Recommended(x) => x.watch(path, mode),
-    }
+// What the block given to `watch` is called with, and what `drain_events`
+// returns for each event: the kind hierarchy, the paths, the raw kind and the
+// keyword arguments for `Watchcat::Event.new`.
+type EventArgs = (Vec<String>, Vec<String>, String, RHash);
+
+fn payload_to_args(ruby: &Ruby, payload: EventPayload) -> Result<EventArgs, Error> {
+    let details = details_to_hash(ruby, payload.details)?;
+    Ok((payload.kinds, payload.paths, payload.raw_kind, details))
 }
 
-fn watcher_unwatch(w: &mut WatcherEnum, path: &Path) -> notify::Result<()> {
-    match w {
-        WatcherEnum::Poll(x) => x.unwatch(path),
-        WatcherEnum::Recommended(x) => x.unwatch(path),
+// Why `run_loop` returned.
+enum LoopExit {
+    Closed,
+    // Woken up by the unblock function of `watch` so that Ruby can handle an
+    // interrupt. The loop can be run again afterwards.
+    Interrupted,
+}
+
+// Runs the watch loop until the watcher is closed, handing each event to
+// `deliver`. Must be called without the GVL.
+fn run_loop(
+    session: &mut WatchSession,
+    terminated: &AtomicBool,
+    rx: &crossbeam_channel::Receiver<bool>,
+    cmd_rx: &crossbeam_channel::Receiver<Command>,
+    watcher_rx: &crossbeam_channel::Receiver<notify::Result<notify::Event>>,
+    mut deliver: impl FnMut(EventPayload) -> Result<(), String>,
+) -> Result<LoopExit, WatchFailure> {
+    loop {
+        if terminated.load(Ordering::SeqCst) {
+            break Ok(LoopExit::Closed);
+        }
+
+        // Fires when events held back by `atomic_saves` or `normalize` are
+        // due.
+        let tick = match session.next_deadline() {
+            Some(deadline) => at(deadline),
+            None => never(),
+        };
+
+        // Signalled once what `Watchcat::Testing.inject` sent is delivered.
+        let mut injected = None;
+
+        let reports = select! {
+            // `close` sends `true`, the unblock function `false`.
+            recv(rx) -> msg => {
+                match msg {
+                    Ok(false) => {
+                        log!(Debug, "interrupted");
+                        break Ok(LoopExit::Interrupted);
+                    }
+                    _ => {
+                        log!(Info, "closed");
+                        break Ok(LoopExit::Closed);
+                    }
+                }
+            }
+            recv(cmd_rx) -> cmd => {
+                match cmd {
+                    Ok(Command::Inject(event, done)) => {
+                        injected = Some(done);
+                        session.inject(event)
+                    }
+                    Ok(cmd) => {
+                        log!(Debug, "command", command = format!("{cmd:?}"));
+                        session.handle(cmd);
+                        Vec::new()
+                    }
+                    Err(_) => Vec::new(),
+                }
+            }
+            recv(watcher_rx) -> res => {
+                match res {
+                    Ok(Ok(event)) => session.process(event),
+                    Ok(Err(e)) => {
+                        log!(Error, "watch failed", error = e);
+                        break Err(WatchFailure::Runtime(e.to_string()));
+                    }
+                    Err(e) => {
+                        log!(Error, "watch failed", error = e);
+                        break Err(WatchFailure::Runtime(e.to_string()));
+                    }
+                }
+            }
+            recv(tick) -> _ => session.flush(),
+        };
+
+        let delivered = reports
+            .into_iter()
+            .try_for_each(|report| deliver(session.describe(report)));
+        if let Err(msg) = delivered {
+            log!(Error, "delivery failed", error = msg);
+            break Err(WatchFailure::Runtime(msg));
+        }
+        if let Some(done) = injected {
+            let _ = done.send(());
+        }
     }
 }
 
-// Carries a failure out of the GVL-released section without touching Ruby.
-// `magnus::Error` (and the `Ruby` handle needed to build one) must only be
-// used while the GVL is held, so the actual `magnus::Error` is constructed
-// after control returns from `call_without_gvl`.
-enum WatchFailure {
-    Arg(String),
-    Runtime(String),
+fn details_to_hash(ruby: &Ruby, details: EventDetails) -> Result<RHash, Error> {
+    let hash = ruby.hash_new();
+    hash.aset(ruby.sym_new("root"), details.root)?;
+    hash.aset(ruby.sym_new("relative_paths"), details.relative_paths)?;
+    hash.aset(ruby.sym_new("resolved_paths"), details.resolved_paths)?;
+
+    let attributes = ruby.hash_new();
+    attributes.aset(ruby.sym_new("tracker"), details.attributes.tracker)?;
+    attributes.aset(ruby.sym_new("flag"), details.attributes.flag)?;
+    attributes.aset(ruby.sym_new("info"), details.attributes.info)?;
+    attributes.aset(ruby.sym_new("source"), details.attributes.source)?;
+    attributes.aset(ruby.sym_new("process_id"), details.attributes.process_id)?;
+    hash.aset(ruby.sym_new("attributes"), attributes)?;
+    hash.aset(ruby.sym_new("process_name"), details.process_name)?;
+    hash.aset(ruby.sym_new("time"), details.time)?;
+
+    if let Some(stats) = details.stats {
+        let array = ruby.ary_new();
+        for stat in stats {
+            array.push(stat.map(|stat| stat_to_hash(ruby, stat)).transpose()?)?;
+        }
+        hash.aset(ruby.sym_new("stats"), array)?;
+    }
+    if let Some(diff) = details.diff {
+        let hunks = diff
+            .hunks
+            .into_iter()
+            .map(|((old_start, old_end), (new_start, new_end))| vec![vec![old_start, old_end], vec![new_start, new_end]])
+            .collect::<Vec<_>>();
+        let hash_diff = ruby.hash_new();
+        hash_diff.aset(ruby.sym_new("unified"), diff.unified)?;
+        hash_diff.aset(ruby.sym_new("hunks"), hunks)?;
+        hash.aset(ruby.sym_new("diff"), hash_diff)?;
+    }
+    if let Some(chunks) = details.appended {
+        let array = ruby.ary_new();
+        for chunk in chunks {
+            array.push(ruby.str_from_slice(&chunk))?;
+        }
+        hash.aset(ruby.sym_new("appended"), array)?;
+    }
+
+    if let Some((kind, directory)) = details.normalized {
+        hash.aset(ruby.sym_new("normalized"), kind)?;
+        hash.aset(ruby.sym_new("directory"), directory)?;
+    }
+    Ok(hash)
 }
 
-enum Command {
-    Watch(Vec<String>, bool),   // paths, recursive
-    Unwatch(Vec<String>),       // paths
+fn stat_to_hash(ruby: &Ruby, stat: FileStat) -> Result<RHash, Error> {
+    let hash = ruby.hash_new();
+    hash.aset(ruby.sym_new("size"), stat.size)?;
+    hash.aset(ruby.sym_new("mtime"), stat.mtime)?;
+    hash.aset(ruby.sym_new("mode"), stat.mode)?;
+    hash.aset(ruby.sym_new("ino"), stat.ino)?;
+    hash.aset(ruby.sym_new("type"), stat.file_type)?;
+    Ok(hash)
 }
 
 impl WatchcatWatcher {
@@ -65,53 +229,46 @@ impl WatchcatWatcher {
             terminated: Arc::new(AtomicBool::new(false)),
             cmd_tx,
             cmd_rx,
+            queue: OnceLock::new(),
         }
     }
 
-    fn close(&self) {
-        self.terminated.store(true, Ordering::SeqCst);
-        // See `add`/`unwatch`: `send` cannot fail while `self` retains `rx`,
-        // but `.unwrap()` would still turn a hypothetical failure into a
-        // Rust panic, and a panic crossing the FFI boundary aborts the whole
-        // process instead of raising in Ruby. Not worth the risk for a
-        // result we already know.
-        let _ = self.tx.send(true);
+    fn close(&self) -> Result<(), Error> {
+        guard(|| {
+            self.terminated.store(true, Ordering::SeqCst);
+            // See `add`/`unwatch`: `send` cannot fail while `self` retains `rx`,
+            // and `.unwrap()` would only turn a hypothetical failure into a
+            // `Watchcat::InternalError`. Not worth it for a result we already
+            // know.
+            let _ = self.tx.send(true);
+            Ok(())
+        })
     }
 
     fn watch(&self, args: &[Value]) -> Result<bool, Error> {
-        let ruby = unsafe { Ruby::get_unchecked() };
-        let ruby_ref = &ruby;
-        if !ruby_ref.block_given() {
-            return Err(Error::new(ruby_ref.exception_arg_error(), "no block given"));
-        }
+        guard(|| {
+            let ruby = unsafe { Ruby::get_unchecked() };
+            let ruby_ref = &ruby;
+            if !ruby_ref.block_given() {
+                return Err(Error::new(ruby_ref.exception_arg_error(), "no block given"));
+            }
 
-        let (pathnames, recursive, force_polling, poll_interval, ignore_remove, ignore_access, ignore_create, ignore_modify) = Self::parse_args(args)?;
-        let mode = if recursive {
-            RecursiveMode::Recursive
-        } else {
-            RecursiveMode::NonRecursive
-        };
+            let (pathnames, options) = Self::parse_args(args)?;
 
-        let terminated = self.terminated.clone();
-        let rx_clone = self.rx.clone();
-        let cmd_rx = self.cmd_rx.clone();
+            let terminated = self.terminated.clone();
+            let tx_clone = self.tx.clone();
+            let rx_clone = self.rx.clone();
+            let cmd_rx = self.cmd_rx.clone();
 
-        Self::watch_threaded(
-            pathnames, mode, force_polling, poll_interval, ignore_remove, ignore_access, ignore_create, ignore_modify, terminated, rx_clone, cmd_rx, ruby_ref
-        )
+            Self::watch_threaded(pathnames, options, terminated, tx_clone, rx_clone, cmd_rx, ruby_ref)
+        })
     }
 
-    #[allow(clippy::too_many_arguments)]
     fn watch_threaded(
         pathnames: Vec<String>,
-        mode: RecursiveMode,
-        force_polling: bool,
-        poll_interval: u64,
-        ignore_remove: bool,
-        ignore_access: bool,
-        ignore_create: bool,
-        ignore_modify: bool,
+        options: WatchOptions,
         terminated: Arc<AtomicBool>,
+        tx: crossbeam_channel::Sender<bool>,
         rx: crossbeam_channel::Receiver<bool>,
         cmd_rx: crossbeam_channel::Receiver<Command>,
         ruby: &Ruby
@@ -121,136 +278,284 @@ impl WatchcatWatcher {
         // `call_without_gvl` closure below. Failures are carried out as plain
         // `WatchFailure` values and converted to a real `magnus::Error` afterwards,
         // once control has returned here with the GVL held again.
-        let result: Result<bool, WatchFailure> = call_without_gvl(move || {
+        let (watcher_tx, watcher_rx) = unbounded();
+        // This variable is needed to keep the watcher active.
+        let mut session = call_without_gvl(move || WatchSession::new(&pathnames, options, watcher_tx))
+            .map_err(|e| Error::new(ruby.exception_arg_error(), e.to_string()))?;
+
+        loop {
+            let result = call_without_gvl_interruptible(
+                || {
+                    run_loop(&mut session, &terminated, &rx, &cmd_rx, &watcher_rx, |payload| {
+                        // Yield to Ruby with GVL
+                        call_with_gvl(|ruby| {
+                            let args = payload_to_args(&ruby, payload)?;
+                            ruby.yield_value::<EventArgs, Value>(args).map(|_| ())
+                        })
+                        .map_err(|msg| format!("Error yielding to Ruby block: {msg}"))
+                    })
+                },
+                // Called by Ruby to interrupt the loop for `Thread#kill`,
+                // `Thread#raise` or a signal.
+                || {
+                    let _ = tx.send(false);
+                },
+            );
+
+            match result {
+                Ok(LoopExit::Closed) => break Ok(true),
+                // Raises (or unwinds, for `Thread#kill`) if the interrupt
+                // calls for it. Otherwise, e.g. after a trap handler has run,
+                // keeps watching.
+                Ok(LoopExit::Interrupted) => ruby.thread_check_ints()?,
+                Err(WatchFailure::Runtime(msg)) => break Err(Error::new(ruby.exception_runtime_error(), msg)),
+            }
+        }
+    }
+
+    // Like `watch`, but returns right away: events are queued for
+    // `drain_events` by a native thread, and the returned file descriptor
+    // becomes readable whenever there are some, so that no Ruby thread has to
+    // block on the watcher.
+    fn start(&self, args: &[Value]) -> Result<i32, Error> {
+        guard(|| {
+            let ruby = unsafe { Ruby::get_unchecked() };
+            let (pathnames, options) = Self::parse_args(args)?;
+            let already_started = || Error::new(ruby.exception_runtime_error(), "watcher already started");
+            if self.queue.get().is_some() {
+                return Err(already_started());
+            }
+
+            let queue = EventQueue::new().map_err(|e| {
+                let class = if e.kind() == std::io::ErrorKind::Unsupported {
+                    ruby.exception_not_imp_error()
+                } else {
+                    ruby.exception_runtime_error()
+                };
+                Error::new(class, e.to_string())
+            })?;
+            let queue = Arc::new(queue);
+
             let (tx, watcher_rx) = unbounded();
-            // This variable is needed to keep `watcher` active.
-            let mut _watcher = match force_polling {
-                true => {
-                    let delay = Duration::from_millis(poll_interval);
-                    let config = notify::Config::default().with_poll_interval(delay);
-                    let mut watcher = PollWatcher::new(tx, config)
-                        .map_err(|e| WatchFailure::Arg(e.to_string()))?;
-                    for pathname in &pathnames {
-                        let path = Path::new(pathname);
-                        watcher
-                            .watch(path, mode)
-                            .map_err(|e| WatchFailure::Arg(e.to_string()))?;
-                    }
-                    WatcherEnum::Poll(watcher)
-                }
-                false => {
-                    let mut watcher = RecommendedWatcher::new(tx, Config::default())
-                        .map_err(|e| WatchFailure::Arg(e.to_string()))?;
-                    for pathname in &pathnames {
-                        let path = Path::new(pathname);
-                        watcher
-                            .watch(path, mode)
-                            .map_err(|e| WatchFailure::Arg(e.to_string()))?;
+            // Setting up the watches walks the watched trees, so it is done
+            // without the GVL as well.
+            let session = call_without_gvl(move || WatchSession::new(&pathnames, options, tx))
+                .map_err(|e| Error::new(ruby.exception_arg_error(), e.to_string()))?;
+            // Only once the session is set up: a watcher that failed to start
+            // has no queue whose end `fileno` and `drain_events` would wait for
+            // in vain, and can be started again.
+            if self.queue.set(queue.clone()).is_err() {
+                return Err(already_started());
+            }
+
+            let terminated = self.terminated.clone();
+            let rx = self.rx.clone();
+            let cmd_rx = self.cmd_rx.clone();
+            let fd = queue.fd();
+            std::thread::spawn(move || {
+                let mut session = session;
+                // Nothing above this thread could catch a panic, and `drain_events`
+                // would wait forever for the loop to end.
+                let result = catch_unwind(AssertUnwindSafe(|| {
+                    run_loop(&mut session, &terminated, &rx, &cmd_rx, &watcher_rx, |payload| {
+                        queue.push(payload);
+                        Ok(())
+                    })
+                }));
+                queue.finish(match result {
+                    Ok(Ok(_)) => Ending::Closed,
+                    Ok(Err(WatchFailure::Runtime(msg))) => Ending::Failed(msg),
+                    Err(payload) => {
+                        let msg = panic_message(&*payload);
+                        log!(Error, "panic in watch loop", error = msg);
+                        Ending::Panicked(msg)
                     }
-                    WatcherEnum::Recommended(watcher)
+                });
+            });
+
+            Ok(fd)
+        })
+    }
+
+    // The descriptor `start` returned. `Watchcat::Watcher#to_io` wraps it.
+    fn fileno(&self) -> Result<i32, Error> {
+        guard(|| {
+            Ok(self.started_queue()?.fd())
+        })
+    }
+
+    // Returns the events queued since the last call, without blocking.
+    // `nil` once the watcher started by `start` has been closed and every
+    // event has been taken. `Watchcat::Watcher#drain_events` turns them into
+    // `Watchcat::Event`s.
+    fn drain_raw_events(&self) -> Result<Option<Vec<EventArgs>>, Error> {
+        guard(|| {
+            let ruby = unsafe { Ruby::get_unchecked() };
+            let queue = self.started_queue()?;
+
+            match queue.drain() {
+                Drained::Events(events) => events
+                    .into_iter()
+                    .map(|payload| payload_to_args(&ruby, payload))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map(Some),
+                Drained::Ended(Ending::Closed) => Ok(None),
+                Drained::Ended(Ending::Failed(msg)) => Err(Error::new(ruby.exception_runtime_error(), msg)),
+                Drained::Ended(Ending::Panicked(msg)) => Err(internal_error(&ruby, &msg)),
+            }
+        })
+    }
+
+    // Blocks until an event of `kind` ("any" for every kind) on `path`, or on
+    // a path below it matching `pattern`, arrives, and returns it. `nil` once
+    // `timeout` seconds have passed. `Watchcat.wait_for` wraps it.
+    fn wait_for(path: String, kind: String, timeout: Option<f64>, pattern: Option<String>) -> Result<Option<EventArgs>, Error> {
+        guard(|| {
+            let ruby = unsafe { Ruby::get_unchecked() };
+            let deadline = match timeout {
+                Some(secs) => {
+                    let timeout = Duration::try_from_secs_f64(secs)
+                        .map_err(|e| Error::new(ruby.exception_arg_error(), format!("invalid timeout: {e}")))?;
+                    Some(Instant::now() + timeout)
                 }
+                None => None,
             };
+            let target = WaitTarget {
+                path: PathBuf::from(path),
+                kind: Some(kind).filter(|kind| kind != "any"),
+                pattern,
+            };
+
+            let mut waiter = call_without_gvl(move || Waiter::new(target, deadline))
+                .map_err(|e| Error::new(ruby.exception_arg_error(), e.to_string()))?;
+            let (cancel_tx, cancel_rx) = unbounded();
 
             loop {
-                if terminated.load(Ordering::SeqCst) {
-                    break Ok(true);
+                let result = call_without_gvl_interruptible(
+                    || waiter.wait(&cancel_rx),
+                    || {
+                        let _ = cancel_tx.send(());
+                    },
+                );
+
+                match result {
+                    Ok(Waited::Event(payload)) => break payload_to_args(&ruby, *payload).map(Some),
+                    Ok(Waited::TimedOut) => break Ok(None),
+                    // See `watch_threaded`.
+                    Ok(Waited::Interrupted) => ruby.thread_check_ints()?,
+                    Err(msg) => break Err(Error::new(ruby.exception_runtime_error(), msg)),
                 }
+            }
+        })
+    }
 
-                select! {
-                    recv(rx) -> _res => {
-                        break Ok(true);
-                    }
-                    recv(cmd_rx) -> cmd => {
-                        if let Ok(cmd) = cmd {
-                            match cmd {
-                                Command::Watch(paths, recursive) => {
-                                    let m = if recursive { RecursiveMode::Recursive } else { RecursiveMode::NonRecursive };
-                                    for p in &paths {
-                                        let _ = watcher_watch(&mut _watcher, Path::new(p), m);
-                                    }
-                                }
-                                Command::Unwatch(paths) => {
-                                    for p in &paths {
-                                        let _ = watcher_unwatch(&mut _watcher, Path::new(p));
-                                    }
-                                }
-                            }
+    // Hands an event to the watch loop of a watcher with `backend: :fake`,
+    // as if the backend had reported it, and with `wait` returns once it has
+    // been delivered to the block (or queued, for `start`).
+    // `Watchcat::Testing.inject` wraps it.
+    fn inject(&self, kind: String, paths: Vec<String>, wait: bool) -> Result<bool, Error> {
+        guard(|| {
+            let ruby = unsafe { Ruby::get_unchecked() };
+            let event = fake::event(&kind, paths).map_err(|msg| Error::new(ruby.exception_arg_error(), msg))?;
+            if self.terminated.load(Ordering::SeqCst) {
+                return Ok(false);
+            }
+
+            let (done_tx, done_rx) = unbounded();
+            // See `add`: `send` cannot fail while `self` retains `cmd_rx`.
+            let _ = self.cmd_tx.send(Command::Inject(event, done_tx));
+            if !wait {
+                return Ok(true);
+            }
+            self.wait_delivered(&ruby, done_rx)
+        })
+    }
+
+    // Feeds a recording made with `record_to` to a watcher with
+    // `backend: :fake`, `speed` times as fast as it was recorded (`nil` for
+    // no pauses), and returns the number of events once all of them have
+    // been delivered. `Watchcat.replay` wraps it.
+    fn replay(&self, file: String, speed: Option<f64>) -> Result<usize, Error> {
+        guard(|| {
+            let ruby = unsafe { Ruby::get_unchecked() };
+            if speed.is_some_and(|speed| !(speed.is_finite() && speed > 0.0)) {
+                return Err(Error::new(ruby.exception_arg_error(), "speed must be a positive number"));
+            }
+            let records = call_without_gvl(|| record::read(Path::new(&file)))
+                .map_err(|msg| Error::new(ruby.exception_arg_error(), msg))?;
+
+            let started = Instant::now();
+            let mut replayed = 0;
+            for record in records {
+                if let Some(speed) = speed {
+                    let due = started + Duration::from_secs_f64(record.t.max(0.0) / speed);
+                    without_gvl_until(&ruby, |cancel| {
+                        select! {
+                            recv(cancel) -> _ => None,
+                            recv(at(due)) -> _ => Some(()),
                         }
+                    })?;
+                }
+
+                // Changes to the watched paths take effect before the events
+                // that follow them, as the loop handles commands in order.
+                let event = match record.entry {
+                    Entry::Event(event) => event,
+                    Entry::Watch { paths, recursive, depth } => {
+                        let _ = self.cmd_tx.send(Command::Watch(paths, recursive, depth));
+                        continue;
                     }
-                    recv(watcher_rx) -> res => {
-                        match res {
-                            Ok(event) => {
-                                match event {
-                                    Ok(event) => {
-                                        let paths = event
-                                            .paths
-                                            .iter()
-                                            .map(|p| p.to_string_lossy().into_owned())
-                                            .collect::<Vec<_>>();
-
-                                        if ignore_remove && matches!(event.kind, notify::event::EventKind::Remove(_)) {
-                                            continue;
-                                        }
-
-                                        let macos_ambiguous_metadata_touch = cfg!(target_os = "macos")
-                                            && matches!(
-                                                event.kind,
-                                                notify::event::EventKind::Modify(
-                                                    notify::event::ModifyKind::Metadata(
-                                                        notify::event::MetadataKind::Any
-                                                    )
-                                                )
-                                            );
-                                        if ignore_access
-                                            && (matches!(
-                                                event.kind,
-                                                notify::event::EventKind::Access(_)
-                                            ) || macos_ambiguous_metadata_touch)
-                                        {
-                                            continue;
-                                        }
-                                        if ignore_create && matches!(event.kind, notify::event::EventKind::Create(_)) {
-                                            continue;
-                                        }
-                                        if ignore_modify && matches!(event.kind, notify::event::EventKind::Modify(_)) {
-                                            continue;
-                                        }
-
-                                        // Yield to Ruby with GVL
-                                        let result: Result<Value, String> = call_with_gvl(|ruby| {
-                                            ruby.yield_value::<(Vec<String>, Vec<String>, String), Value>(
-                                                (WatchatEvent::convert_kind(&event.kind), paths, format!("{:?}", event.kind))
-                                            ).map_err(|e| e.to_string())
-                                        });
-
-                                        if let Err(msg) = result {
-                                            break Err(WatchFailure::Runtime(format!("Error yielding to Ruby block: {msg}")));
-                                        }
-                                    }
-                                    Err(e) => {
-                                        break Err(WatchFailure::Runtime(e.to_string()));
-                                    }
-                                }
-                            }
-                            Err(e) => {
-                                break Err(WatchFailure::Runtime(e.to_string()));
-                            }
-                        }
+                    Entry::Unwatch { paths } => {
+                        let _ = self.cmd_tx.send(Command::Unwatch(paths));
+                        continue;
                     }
+                };
+                let (done_tx, done_rx) = unbounded();
+                let _ = self.cmd_tx.send(Command::Inject(event, done_tx));
+                if !self.wait_delivered(&ruby, done_rx)? {
+                    break;
                 }
+                replayed += 1;
             }
-        });
+            Ok(replayed)
+        })
+    }
+
+    // Waits for the watch loop to deliver an injected event. `false` if the
+    // watcher was closed first.
+    fn wait_delivered(&self, ruby: &Ruby, done: crossbeam_channel::Receiver<()>) -> Result<bool, Error> {
+        if self.terminated.load(Ordering::SeqCst) {
+            return Ok(false);
+        }
 
-        result.map_err(|err| match err {
-            WatchFailure::Arg(msg) => Error::new(ruby.exception_arg_error(), msg),
-            WatchFailure::Runtime(msg) => Error::new(ruby.exception_runtime_error(), msg),
+        let terminated = &*self.terminated;
+        without_gvl_until(ruby, |cancel| loop {
+            // A command sent after the loop ended is never picked up, so
+            // `close` is watched for as well.
+            select! {
+                recv(done) -> res => break Some(res.is_ok()),
+                recv(cancel) -> _ => break None,
+                default(Duration::from_millis(50)) => {
+                    if terminated.load(Ordering::SeqCst) {
+                        break Some(false);
+                    }
+                }
+            }
+        })
+    }
+
+    fn started_queue(&self) -> Result<&Arc<EventQueue>, Error> {
+        self.queue.get().ok_or_else(|| {
+            let ruby = unsafe { Ruby::get_unchecked() };
+            Error::new(ruby.exception_runtime_error(), "watcher not started")
         })
     }
 
     #[allow(clippy::let_unit_value, clippy::type_complexity)]
-    fn parse_args(args: &[Value]) -> Result<(Vec<String>, bool, bool, u64, bool, bool, bool, bool), Error> {
+    fn parse_args(args: &[Value]) -> Result<(Vec<String>, WatchOptions), Error> {
         type KwArgBool = Option<Option<bool>>;
         type KwArgU64 = Option<Option<u64>>;
+        type KwArgUsize = Option<Option<usize>>;
 
         let args = scan_args(args)?;
         let (paths,): (Vec<String>,) = args.required;
@@ -259,48 +564,105 @@ impl WatchcatWatcher {
         let _: () = args.trailing;
         let _: () = args.block;
 
+        // Split up because `get_kwargs` takes at most 9 optional keywords.
+        // Each call leaves the keywords it doesn't know in `rest`; the last
+        // rejects anything left over.
         let kwargs = get_kwargs(
             args.keywords,
             &[],
-            &["recursive", "force_polling", "poll_interval", "ignore_remove", "ignore_access", "ignore_create", "ignore_modify"],
+            &["recursive", "force_polling", "poll_interval", "follow_symlinks", "depth", "atomic_saves", "normalize", "stat", "tail"],
+        )?;
+        let (recursive, force_polling, poll_interval, follow_symlinks, depth, atomic_saves, normalize, stat, tail): (KwArgBool, KwArgBool, KwArgU64, KwArgBool, KwArgUsize, KwArgBool, KwArgBool, KwArgBool, Option<Option<(String, Option<u64>)>>) =
+            kwargs.optional;
+        let _: () = kwargs.required;
+        let rest: RHash = kwargs.splat;
+
+        let kwargs = get_kwargs(
+            rest,
+            &[],
+            &["ignore_remove", "ignore_access", "ignore_create", "ignore_modify", "ignore_hidden", "ignore_editor_temp", "backend", "record_to", "track_content"],
         )?;
-        let (recursive, force_polling, poll_interval, ignore_remove, ignore_access, ignore_create, ignore_modify): (KwArgBool, KwArgBool, KwArgU64, KwArgBool, KwArgBool, KwArgBool, KwArgBool) =
+        let (ignore_remove, ignore_access, ignore_create, ignore_modify, ignore_hidden, ignore_editor_temp, backend, record_to, track_content): (KwArgBool, KwArgBool, KwArgBool, KwArgBool, KwArgBool, KwArgBool, Option<Option<String>>, Option<Option<String>>, Option<Option<(Vec<String>, usize, usize)>>) =
             kwargs.optional;
         let _: () = kwargs.required;
+        let rest: RHash = kwargs.splat;
+
+        let kwargs = get_kwargs(rest, &[], &["allow_missing"])?;
+        let (allow_missing,): (KwArgBool,) = kwargs.optional;
+        let _: () = kwargs.required;
         let _: () = kwargs.splat;
 
+        let backend = match backend.flatten().as_deref() {
+            None | Some("native") => Backend::Native,
+            Some("fake") => Backend::Fake,
+            Some("fanotify") => Backend::Fanotify,
+            Some(other) => {
+                let ruby = unsafe { Ruby::get_unchecked() };
+                return Err(Error::new(ruby.exception_arg_error(), format!("unknown backend: {other}")));
+            }
+        };
+        let force_polling = force_polling.flatten().unwrap_or(false);
+        if backend == Backend::Fanotify && force_polling {
+            let ruby = unsafe { Ruby::get_unchecked() };
+            return Err(Error::new(ruby.exception_arg_error(), "backend: :fanotify can't be used with force_polling: true"));
+        }
+
         Ok((
             paths,
-            recursive.flatten().unwrap_or(false),
-            force_polling.flatten().unwrap_or(false),
-            poll_interval.flatten().unwrap_or(200),
-            ignore_remove.flatten().unwrap_or(false),
-            ignore_access.flatten().unwrap_or(false),
-            ignore_create.flatten().unwrap_or(false),
-            ignore_modify.flatten().unwrap_or(false),
+            WatchOptions {
+                backend,
+                record_to: record_to.flatten().map(PathBuf::from),
+                recursive: recursive.flatten().unwrap_or(false),
+                force_polling,
+                poll_interval: poll_interval.flatten().unwrap_or(200),
+                follow_symlinks: follow_symlinks.flatten(),
+                depth: depth.flatten(),
+                atomic_saves: atomic_saves.flatten().unwrap_or(false),
+                normalize: normalize.flatten().unwrap_or(false),
+                stat: stat.flatten().unwrap_or(false),
+                // Given by `Watchcat::Executor` as
+                // `[patterns, max_bytes, max_total_bytes]`.
+                track_content: track_content
+                    .flatten()
+                    .map(|(patterns, max_bytes, max_total_bytes)| TrackContent { patterns, max_bytes, max_total_bytes }),
+                // Given by `Watchcat.tail` as `[path, offset]`.
+                tail: tail.flatten().map(|(path, offset)| (PathBuf::from(path), offset)),
+                allow_missing: allow_missing.flatten().unwrap_or(false),
+                ignore_remove: ignore_remove.flatten().unwrap_or(false),
+                ignore_access: ignore_access.flatten().unwrap_or(false),
+                ignore_create: ignore_create.flatten().unwrap_or(false),
+                ignore_modify: ignore_modify.flatten().unwrap_or(false),
+                ignore_hidden: ignore_hidden.flatten().unwrap_or(false),
+                ignore_editor_temp: ignore_editor_temp.flatten().unwrap_or(false),
+            },
         ))
     }
 
     fn add(&self, args: &[Value]) -> Result<bool, Error> {
-        let (paths, recursive) = Self::parse_add_args(args)?;
-        // `send` only fails when every receiver is disconnected, but `self`
-        // holds `cmd_rx` for the whole lifetime of this object, so it cannot
-        // fail here. If the watch loop has already stopped, the command is
-        // simply buffered and never applied (a harmless no-op).
-        let _ = self.cmd_tx.send(Command::Watch(paths, recursive));
-        Ok(true)
+        guard(|| {
+            let (paths, recursive, depth) = Self::parse_add_args(args)?;
+            // `send` only fails when every receiver is disconnected, but `self`
+            // holds `cmd_rx` for the whole lifetime of this object, so it cannot
+            // fail here. If the watch loop has already stopped, the command is
+            // simply buffered and never applied (a harmless no-op).
+            let _ = self.cmd_tx.send(Command::Watch(paths, recursive, depth));
+            Ok(true)
+        })
     }
 
     fn unwatch(&self, args: &[Value]) -> Result<bool, Error> {
-        let paths = Self::parse_unwatch_args(args)?;
-        // See `add`: `send` cannot fail while `self` retains `cmd_rx`.
-        let _ = self.cmd_tx.send(Command::Unwatch(paths));
-        Ok(true)
+        guard(|| {
+            let paths = Self::parse_unwatch_args(args)?;
+            // See `add`: `send` cannot fail while `self` retains `cmd_rx`.
+            let _ = self.cmd_tx.send(Command::Unwatch(paths));
+            Ok(true)
+        })
     }
 
     #[allow(clippy::let_unit_value)]
-    fn parse_add_args(args: &[Value]) -> Result<(Vec<String>, bool), Error> {
+    fn parse_add_args(args: &[Value]) -> Result<(Vec<String>, bool, Option<usize>), Error> {
         type KwArgBool = Option<Option<bool>>;
+        type KwArgUsize = Option<Option<usize>>;
 
         let args = scan_args(args)?;
         let (paths,): (Vec<String>,) = args.required;
@@ -309,12 +671,12 @@ impl WatchcatWatcher {
         let _: () = args.trailing;
         let _: () = args.block;
 
-        let kwargs = get_kwargs(args.keywords, &[], &["recursive"])?;
-        let (recursive,): (KwArgBool,) = kwargs.optional;
+        let kwargs = get_kwargs(args.keywords, &[], &["recursive", "depth"])?;
+        let (recursive, depth): (KwArgBool, KwArgUsize) = kwargs.optional;
         let _: () = kwargs.required;
         let _: () = kwargs.splat;
 
-        Ok((paths, recursive.flatten().unwrap_or(true)))
+        Ok((paths, recursive.flatten().unwrap_or(true), depth.flatten()))
     }
 
     #[allow(clippy::let_unit_value)]
@@ -335,16 +697,95 @@ impl WatchcatWatcher {
     }
 }
 
+// Runs `f` without the GVL until it returns something. `f` is handed a
+// channel that is signalled when Ruby wants to interrupt it, and must then
+// return `None`, so that the interrupt can be handled (see `watch_threaded`)
+// before it is run again.
+fn without_gvl_until<F, R>(ruby: &Ruby, mut f: F) -> Result<R, Error>
+where
+    F: Send + FnMut(&crossbeam_channel::Receiver<()>) -> Option<R>,
+{
+    let (cancel_tx, cancel_rx) = unbounded();
+    loop {
+        let result = call_without_gvl_interruptible(
+            || f(&cancel_rx),
+            || {
+                let _ = cancel_tx.send(());
+            },
+        );
+        match result {
+            Some(result) => break Ok(result),
+            None => ruby.thread_check_ints()?,
+        }
+    }
+}
+
+// Sets the level of `Watchcat.logger` (a `Logger` severity), or turns
+// logging off with `nil`.
+fn set_log_level(level: Option<u8>) -> Result<(), Error> {
+    guard(|| {
+        logging::set_level(level);
+        Ok(())
+    })
+}
+
+fn fanotify_available() -> Result<bool, Error> {
+    guard(|| Ok(fanotify::available()))
+}
+
+// Returns the buffered log records as `[severity, message]` pairs. With
+// `wait`, blocks until there are some. `Watchcat::Logging` hands them to
+// `Watchcat.logger`.
+fn take_log_records(wait: bool) -> Result<Vec<(u8, String)>, Error> {
+    guard(|| {
+        let ruby = unsafe { Ruby::get_unchecked() };
+        let records = if wait {
+            without_gvl_until(&ruby, |cancel| logging::take(Some(cancel)))?
+        } else {
+            logging::take(None).unwrap_or_default()
+        };
+        Ok(records.into_iter().map(|(level, message)| (level as u8, message)).collect())
+    })
+}
+
+// Panics on purpose, for testing that a panic raises
+// `Watchcat::InternalError` instead of taking the process down. `site` picks
+// where: "method", "without_gvl" or "with_gvl" (inside `call_without_gvl`).
+fn panic_for_test(site: String) -> Result<(), Error> {
+    guard(|| match site.as_str() {
+        "without_gvl" => call_without_gvl(|| panic!("test panic without the GVL")),
+        "with_gvl" => call_without_gvl(|| call_with_gvl(|_| panic!("test panic with the GVL"))),
+        _ => panic!("test panic"),
+    })
+}
+
 #[magnus::init]
 fn init(ruby: &Ruby) -> Result<(), Error> {
+    // No global Ruby state is kept on the Rust side, and a watch only ever
+    // yields to the block of the Ractor that called `watch`.
+    unsafe { rb_sys::rb_ext_ractor_safe(true) };
+    logging::install_fork_handler();
+
     let module = ruby.define_module("Watchcat")?;
+    // Defines `Watchcat::InternalError`.
+    Lazy::force(&INTERNAL_ERROR, ruby);
+    module.define_singleton_method("__panic_for_test__", function!(panic_for_test, 1))?;
+    module.define_singleton_method("__set_log_level__", function!(set_log_level, 1))?;
+    module.define_singleton_method("__take_log_records__", function!(take_log_records, 1))?;
+    module.define_singleton_method("__fanotify_available__", function!(fanotify_available, 0))?;
 
     let watcher_class = module.define_class("Watcher", ruby.class_object())?;
     watcher_class.define_singleton_method("new", function!(WatchcatWatcher::new, 0))?;
+    watcher_class.define_singleton_method("wait_for", function!(WatchcatWatcher::wait_for, 4))?;
     watcher_class.define_method("watch", method!(WatchcatWatcher::watch, -1))?;
     watcher_class.define_method("close", method!(WatchcatWatcher::close, 0))?;
     watcher_class.define_method("add", method!(WatchcatWatcher::add, -1))?;
     watcher_class.define_method("unwatch", method!(WatchcatWatcher::unwatch, -1))?;
+    watcher_class.define_method("start", method!(WatchcatWatcher::start, -1))?;
+    watcher_class.define_method("fileno", method!(WatchcatWatcher::fileno, 0))?;
+    watcher_class.define_method("inject", method!(WatchcatWatcher::inject, 3))?;
+    watcher_class.define_method("replay", method!(WatchcatWatcher::replay, 2))?;
+    watcher_class.define_method("drain_raw_events", method!(WatchcatWatcher::drain_raw_events, 0))?;
 
     Ok(())
 }
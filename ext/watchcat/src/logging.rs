@@ -0,0 +1,198 @@
+use std::{
+    collections::VecDeque,
+    path::PathBuf,
+    ptr,
+    sync::{
+        atomic::{AtomicPtr, AtomicU8, Ordering},
+        Mutex,
+    },
+};
+
+use crossbeam_channel::{bounded, select, Receiver, Sender};
+
+// Log records are made wherever something happens, mostly without the GVL
+// and on threads Ruby doesn't know about, so they are only buffered here.
+// `Watchcat.logger` is handed them by a Ruby thread that waits for them with
+// `take` (see `Watchcat::Logging`).
+
+// The severities of Ruby's `Logger`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Debug = 0,
+    Info = 1,
+    Warn = 2,
+    Error = 3,
+}
+
+const OFF: u8 = u8::MAX;
+
+// Records beyond this many are dropped (and counted) until the logger catches
+// up, rather than let memory grow without bounds.
+const CAPACITY: usize = 10_000;
+
+static LEVEL: AtomicU8 = AtomicU8::new(OFF);
+
+#[derive(Default)]
+struct Buffer {
+    records: VecDeque<(Level, String)>,
+    dropped: usize,
+}
+
+struct Shared {
+    buffer: Mutex<Buffer>,
+    // Signalled when records are added. Holds at most one signal, as `take`
+    // takes everything there is anyway.
+    ready: (Sender<()>, Receiver<()>),
+}
+
+impl Shared {
+    fn new() -> Self {
+        Self {
+            buffer: Mutex::new(Buffer::default()),
+            ready: bounded(1),
+        }
+    }
+}
+
+// Replaced in a forked child (see `after_fork`), so not a plain static.
+static SHARED: AtomicPtr<Shared> = AtomicPtr::new(ptr::null_mut());
+
+// The parent's buffer in a forked child, freed by the next `shared` call.
+static INHERITED: AtomicPtr<Shared> = AtomicPtr::new(ptr::null_mut());
+
+fn shared() -> &'static Shared {
+    if !INHERITED.load(Ordering::Relaxed).is_null() {
+        let inherited = INHERITED.swap(ptr::null_mut(), Ordering::AcqRel);
+        if !inherited.is_null() {
+            drop(unsafe { Box::from_raw(inherited) });
+        }
+    }
+
+    let current = SHARED.load(Ordering::Acquire);
+    if !current.is_null() {
+        return unsafe { &*current };
+    }
+    let fresh = Box::into_raw(Box::new(Shared::new()));
+    match SHARED.compare_exchange(ptr::null_mut(), fresh, Ordering::AcqRel, Ordering::Acquire) {
+        Ok(_) => unsafe { &*fresh },
+        Err(current) => {
+            drop(unsafe { Box::from_raw(fresh) });
+            unsafe { &*current }
+        }
+    }
+}
+
+// A thread of the parent (a watch loop, the fanotify reader) may have held the
+// buffer's lock when forking, and it would never be released in the child,
+// where that thread doesn't exist. The child starts over with a buffer of its
+// own, made by the first `shared` call: only atomics are touched here, as
+// allocating in a fork handler can deadlock on the allocator's lock. Nothing
+// in the child uses the parent's buffer again, so `shared` frees it whether or
+// not it was locked.
+extern "C" fn after_fork() {
+    let inherited = SHARED.swap(ptr::null_mut(), Ordering::AcqRel);
+    if !inherited.is_null() {
+        INHERITED.store(inherited, Ordering::Release);
+    }
+}
+
+// Runs `after_fork` in every forked child, before anything else can log.
+#[cfg(unix)]
+pub fn install_fork_handler() {
+    unsafe { libc::pthread_atfork(None, None, Some(after_fork)) };
+}
+
+#[cfg(not(unix))]
+pub fn install_fork_handler() {}
+
+// The lowest severity to keep, the level of `Watchcat.logger`. `None` (no
+// logger) turns logging off.
+pub fn set_level(level: Option<u8>) {
+    LEVEL.store(level.unwrap_or(OFF), Ordering::Relaxed);
+    if level.is_none() {
+        let mut buffer = shared().buffer.lock().unwrap_or_else(|e| e.into_inner());
+        *buffer = Buffer::default();
+    }
+}
+
+pub fn enabled(level: Level) -> bool {
+    level as u8 >= LEVEL.load(Ordering::Relaxed)
+}
+
+// Use `log!`, which doesn't build the record unless the level is enabled.
+pub fn push(level: Level, message: &str, fields: &[(&str, String)]) {
+    let mut line = message.to_string();
+    for (key, value) in fields {
+        line.push(' ');
+        line.push_str(key);
+        line.push('=');
+        line.push_str(&quote(value));
+    }
+
+    let shared = shared();
+    {
+        let mut buffer = shared.buffer.lock().unwrap_or_else(|e| e.into_inner());
+        if buffer.records.len() >= CAPACITY {
+            buffer.dropped += 1;
+            return;
+        }
+        buffer.records.push_back((level, line));
+    }
+    let _ = shared.ready.0.try_send(());
+}
+
+// Returns the buffered records, oldest first, waiting for some with `cancel`
+// (until it is signalled, when `None` is returned). Must be called without
+// the GVL when waiting.
+pub fn take(cancel: Option<&Receiver<()>>) -> Option<Vec<(Level, String)>> {
+    let shared = shared();
+    loop {
+        {
+            let mut buffer = shared.buffer.lock().unwrap_or_else(|e| e.into_inner());
+            let dropped = std::mem::take(&mut buffer.dropped);
+            let mut records = buffer.records.drain(..).collect::<Vec<_>>();
+            if dropped > 0 {
+                records.push((Level::Warn, format!("dropped log records count={dropped}")));
+            }
+            if !records.is_empty() {
+                return Some(records);
+            }
+        }
+
+        let cancel = cancel?;
+        select! {
+            recv(shared.ready.1) -> _ => {}
+            recv(cancel) -> _ => return None,
+        }
+    }
+}
+
+// For logging the paths of an event as a single value.
+pub fn paths(paths: &[PathBuf]) -> String {
+    paths.iter().map(|p| p.to_string_lossy()).collect::<Vec<_>>().join(",")
+}
+
+// logfmt-style: values with spaces, quotes or `=` are quoted.
+fn quote(value: &str) -> String {
+    if value.is_empty() || value.contains(|c: char| c.is_whitespace() || c == '"' || c == '=') {
+        format!("{value:?}")
+    } else {
+        value.to_string()
+    }
+}
+
+// `log!(Debug, "message", key = value, ...)`, where each value is anything
+// `Display`.
+macro_rules! log {
+    ($level:ident, $message:expr $(, $key:ident = $value:expr)* $(,)?) => {
+        if $crate::logging::enabled($crate::logging::Level::$level) {
+            $crate::logging::push(
+                $crate::logging::Level::$level,
+                $message,
+                &[$((stringify!($key), $value.to_string())),*],
+            );
+        }
+    };
+}
+
+pub(crate) use log;
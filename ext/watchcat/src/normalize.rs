@@ -0,0 +1,398 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs::{self, Metadata},
+    ops::Bound,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use notify::{
+    event::{CreateKind, DataChange, MetadataKind, ModifyKind, RemoveKind, RenameMode},
+    Event, EventKind,
+};
+use walkdir::WalkDir;
+
+// How long things have to be quiet before dirty paths are looked at.
+const QUIET: Duration = Duration::from_millis(100);
+// Upper bound on how long a change can be held back under a constant stream
+// of events.
+const MAX_DELAY: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Created,
+    Modified,
+    Removed,
+    Moved,
+    MetadataChanged,
+}
+
+impl ChangeKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            ChangeKind::Created => "created",
+            ChangeKind::Modified => "modified",
+            ChangeKind::Removed => "removed",
+            ChangeKind::Moved => "moved",
+            ChangeKind::MetadataChanged => "metadata_changed",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Change {
+    pub kind: ChangeKind,
+    // The path, or the source and destination for `Moved`.
+    pub paths: Vec<PathBuf>,
+    pub directory: bool,
+}
+
+impl Change {
+    fn new(kind: ChangeKind, paths: Vec<PathBuf>, directory: bool) -> Self {
+        Self { kind, paths, directory }
+    }
+
+    /// The notify event closest to this change, so that the rest of the
+    /// pipeline (filters, `Event#kind`) works the same as without `normalize`.
+    pub fn to_event(&self) -> Event {
+        let kind = match self.kind {
+            ChangeKind::Created if self.directory => EventKind::Create(CreateKind::Folder),
+            ChangeKind::Created => EventKind::Create(CreateKind::File),
+            ChangeKind::Removed if self.directory => EventKind::Remove(RemoveKind::Folder),
+            ChangeKind::Removed => EventKind::Remove(RemoveKind::File),
+            ChangeKind::Modified => EventKind::Modify(ModifyKind::Data(DataChange::Any)),
+            ChangeKind::Moved => EventKind::Modify(ModifyKind::Name(RenameMode::Both)),
+            ChangeKind::MetadataChanged => EventKind::Modify(ModifyKind::Metadata(MetadataKind::Any)),
+        };
+        self.paths
+            .iter()
+            .fold(Event::new(kind), |event, path| event.add_path(path.clone()))
+    }
+}
+
+// What is remembered about a path to tell how it changed.
+#[derive(Debug, Clone, PartialEq)]
+struct Meta {
+    id: Option<(u64, u64)>,
+    dir: bool,
+    mtime: Option<SystemTime>,
+    len: u64,
+    mode: u32,
+}
+
+impl Meta {
+    fn read(path: &Path) -> Option<Self> {
+        let metadata = fs::symlink_metadata(path).ok()?;
+        Some(Self {
+            id: file_id(&metadata),
+            dir: metadata.is_dir(),
+            mtime: metadata.modified().ok(),
+            len: metadata.len(),
+            mode: mode(&metadata),
+        })
+    }
+
+    fn compare(&self, new: &Meta) -> Option<ChangeKind> {
+        // A directory's mtime and size change with its entries, which are
+        // reported on their own (and not at all by some backends).
+        if !self.dir && (self.id != new.id || self.mtime != new.mtime || self.len != new.len) {
+            Some(ChangeKind::Modified)
+        } else if self.mode != new.mode {
+            Some(ChangeKind::MetadataChanged)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(unix)]
+fn file_id(metadata: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_metadata: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+#[cfg(unix)]
+fn mode(metadata: &Metadata) -> u32 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.mode()
+}
+
+#[cfg(not(unix))]
+fn mode(metadata: &Metadata) -> u32 {
+    metadata.permissions().readonly() as u32
+}
+
+/// Turns backend events into a backend-independent stream of changes.
+///
+/// Events are only taken as a hint of which paths to look at. Once the
+/// events stop for a moment, each of those paths is compared with what was
+/// known about it before, so the result doesn't depend on how a backend
+/// splits or labels an operation. A path removed and another created with
+/// the same inode in the same batch are reported as a move.
+///
+/// Paths are kept in their canonical form, as backends disagree on which form
+/// they report (FSEvents resolves `/var` to `/private/var`, inotify reports
+/// absolute paths under a relative root), and changes are reported under
+/// the root as it was given.
+#[derive(Debug)]
+pub struct Normalizer {
+    snapshot: BTreeMap<PathBuf, Meta>,
+    roots: Vec<NormalizedRoot>,
+    follow_links: bool,
+    dirty: BTreeSet<PathBuf>,
+    // When the first and the last path of the pending batch got dirty.
+    dirty_since: Option<(Instant, Instant)>,
+}
+
+// A watched root, as given, absolute and canonical, with how many levels
+// below it are watched.
+#[derive(Debug)]
+struct NormalizedRoot {
+    given: PathBuf,
+    location: PathBuf,
+    canonical: PathBuf,
+    max_depth: Option<usize>,
+}
+
+impl Normalizer {
+    pub fn new(follow_links: bool) -> Self {
+        Self {
+            snapshot: BTreeMap::new(),
+            roots: Vec::new(),
+            follow_links,
+            dirty: BTreeSet::new(),
+            dirty_since: None,
+        }
+    }
+
+    /// Records the current state of everything under `root`, which is at
+    /// `location` (where a relative `root` was when it was given).
+    pub fn scan(&mut self, root: &Path, location: &Path, max_depth: Option<usize>) {
+        let canonical = canonicalize(location);
+        self.roots.retain(|r| r.given != root);
+        self.roots.push(NormalizedRoot {
+            given: root.to_path_buf(),
+            location: location.to_path_buf(),
+            canonical: canonical.clone(),
+            max_depth,
+        });
+        for (path, meta) in self.walk(&canonical) {
+            self.snapshot.insert(path, meta);
+        }
+    }
+
+    pub fn forget(&mut self, root: &Path) {
+        let Some(i) = self.roots.iter().position(|r| r.given == root) else {
+            return;
+        };
+        let canonical = self.roots.remove(i).canonical;
+        self.snapshot.retain(|path, _| !path.starts_with(&canonical));
+        self.dirty.retain(|path| !path.starts_with(&canonical));
+    }
+
+    pub fn process(&mut self, event: &Event, now: Instant) -> Vec<Change> {
+        if !matches!(event.kind, EventKind::Access(_)) && !event.paths.is_empty() {
+            let paths = event.paths.iter().map(|p| self.to_canonical(p)).collect::<Vec<_>>();
+            self.dirty.extend(paths);
+            let first = self.dirty_since.map_or(now, |(first, _)| first);
+            self.dirty_since = Some((first, now));
+        }
+        self.expire(now)
+    }
+
+    pub fn expire(&mut self, now: Instant) -> Vec<Change> {
+        match self.next_deadline() {
+            Some(deadline) if deadline <= now => self.evaluate(),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.dirty_since
+            .map(|(first, last)| (last + QUIET).min(first + MAX_DELAY))
+    }
+
+    fn evaluate(&mut self) -> Vec<Change> {
+        self.dirty_since = None;
+        let dirty = std::mem::take(&mut self.dirty);
+
+        let mut removed: BTreeMap<PathBuf, Meta> = BTreeMap::new();
+        let mut created: BTreeMap<PathBuf, Meta> = BTreeMap::new();
+        let mut changes = Vec::new();
+        for path in dirty {
+            if !self.covers(&path) {
+                continue;
+            }
+            match (self.snapshot.get(&path).cloned(), Meta::read(&path)) {
+                (Some(old), None) => {
+                    removed.insert(path, old);
+                }
+                (None, Some(new)) => {
+                    created.insert(path, new);
+                }
+                (Some(old), Some(new)) if old.dir != new.dir => {
+                    removed.insert(path.clone(), old);
+                    created.insert(path, new);
+                }
+                (Some(old), Some(new)) => {
+                    if let Some(kind) = old.compare(&new) {
+                        changes.push(Change::new(kind, vec![path.clone()], new.dir));
+                    }
+                    self.snapshot.insert(path, new);
+                }
+                (None, None) => {}
+            }
+        }
+
+        // Everything below a removed directory is gone as well, and
+        // everything below a created one is new, whether or not the backend
+        // reported it.
+        for (path, meta) in removed.clone() {
+            if meta.dir {
+                for (child, meta) in descendants(&self.snapshot, &path) {
+                    removed.entry(child.clone()).or_insert_with(|| meta.clone());
+                }
+            }
+        }
+        for (path, meta) in created.clone() {
+            if meta.dir {
+                for (child, meta) in self.walk(&path) {
+                    if !self.snapshot.contains_key(&child) {
+                        created.entry(child).or_insert(meta);
+                    }
+                }
+            }
+        }
+
+        let mut moves = Vec::new();
+        let created_ids: HashMap<(u64, u64), PathBuf> = created
+            .iter()
+            .filter_map(|(path, meta)| meta.id.map(|id| (id, path.clone())))
+            .collect();
+        let sources: Vec<PathBuf> = removed.keys().cloned().collect();
+        for src in sources {
+            let Some(meta) = removed.get(&src).cloned() else {
+                // Moved along with its parent.
+                continue;
+            };
+            let Some(dest) = meta.id.and_then(|id| created_ids.get(&id)) else {
+                continue;
+            };
+            let Some(dest_meta) = created.get(dest).cloned().filter(|m| m.dir == meta.dir) else {
+                continue;
+            };
+
+            removed.remove(&src);
+            created.remove(dest);
+            self.snapshot.remove(&src);
+            if meta.dir {
+                // Report the directory, not each entry in it.
+                removed.retain(|path, _| !path.starts_with(&src));
+                created.retain(|path, _| !path.starts_with(dest));
+                for (child, meta) in descendants(&self.snapshot, &src) {
+                    self.snapshot.remove(&child);
+                    if let Ok(rest) = child.strip_prefix(&src) {
+                        self.snapshot.insert(dest.join(rest), meta);
+                    }
+                }
+            }
+            self.snapshot.insert(dest.clone(), dest_meta);
+            moves.push(Change::new(ChangeKind::Moved, vec![src, dest.clone()], meta.dir));
+        }
+
+        let mut result = moves;
+        // Deepest first, the order they can be removed in.
+        for (path, meta) in removed.into_iter().rev() {
+            self.snapshot.remove(&path);
+            result.push(Change::new(ChangeKind::Removed, vec![path], meta.dir));
+        }
+        // Parents first, the order they can be created in.
+        for (path, meta) in created {
+            result.push(Change::new(ChangeKind::Created, vec![path.clone()], meta.dir));
+            self.snapshot.insert(path, meta);
+        }
+        result.extend(changes);
+        for change in &mut result {
+            for path in &mut change.paths {
+                *path = self.to_given(path);
+            }
+        }
+        result
+    }
+
+    // Whether changes to `path` are reported at all.
+    fn covers(&self, path: &Path) -> bool {
+        self.roots.iter().any(|root| match path.strip_prefix(&root.canonical) {
+            Ok(relative) => root.max_depth.is_none_or(|max| relative.components().count() <= max),
+            Err(_) => false,
+        })
+    }
+
+    // An event path in canonical form, whichever form of its root the
+    // backend reported it under.
+    fn to_canonical(&self, path: &Path) -> PathBuf {
+        self.roots
+            .iter()
+            .find_map(|root| {
+                let relative = path
+                    .strip_prefix(&root.given)
+                    .or_else(|_| path.strip_prefix(&root.location))
+                    .ok()?;
+                Some(join(&root.canonical, relative))
+            })
+            .unwrap_or_else(|| path.to_path_buf())
+    }
+
+    // A canonical path under the (most specific) root it's in, as given.
+    fn to_given(&self, path: &Path) -> PathBuf {
+        self.roots
+            .iter()
+            .filter_map(|root| Some((root, path.strip_prefix(&root.canonical).ok()?)))
+            .max_by_key(|(root, _)| root.canonical.components().count())
+            .map_or_else(|| path.to_path_buf(), |(root, relative)| join(&root.given, relative))
+    }
+
+    fn walk(&self, dir: &Path) -> Vec<(PathBuf, Meta)> {
+        WalkDir::new(dir)
+            .follow_links(self.follow_links)
+            .into_iter()
+            .filter_entry(|entry| self.covers(entry.path()))
+            .filter_map(Result::ok)
+            .filter_map(|entry| Meta::read(entry.path()).map(|meta| (entry.into_path(), meta)))
+            .collect()
+    }
+}
+
+// `path` with the symlinks in it resolved, as far as it exists.
+fn canonicalize(path: &Path) -> PathBuf {
+    path.ancestors()
+        .find_map(|ancestor| {
+            let canonical = ancestor.canonicalize().ok()?;
+            Some(join(&canonical, path.strip_prefix(ancestor).ok()?))
+        })
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+// Unlike `Path::join`, doesn't add a trailing separator for an empty
+// `relative`.
+fn join(base: &Path, relative: &Path) -> PathBuf {
+    if relative.as_os_str().is_empty() {
+        base.to_path_buf()
+    } else {
+        base.join(relative)
+    }
+}
+
+// The entries of `snapshot` below `dir`, which sort right after it.
+fn descendants(snapshot: &BTreeMap<PathBuf, Meta>, dir: &Path) -> Vec<(PathBuf, Meta)> {
+    snapshot
+        .range::<Path, _>((Bound::Excluded(dir), Bound::Unbounded))
+        .take_while(|(path, _)| path.starts_with(dir))
+        .map(|(path, meta)| (path.clone(), meta.clone()))
+        .collect()
+}
@@ -0,0 +1,84 @@
+use std::{
+    collections::VecDeque,
+    io,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+use crate::event::EventPayload;
+use crate::signal::ReadySignal;
+
+/// Events collected by a watch loop running on its own thread (see
+/// `Watchcat::Watcher#start`), for Ruby to pick up whenever `fd` becomes
+/// readable.
+#[derive(Debug)]
+pub struct EventQueue {
+    events: Mutex<VecDeque<EventPayload>>,
+    // How the loop ended, once it has.
+    finished: Mutex<Option<Ending>>,
+    signal: ReadySignal,
+}
+
+/// How a watch loop ended.
+#[derive(Debug, Clone)]
+pub enum Ending {
+    Closed,
+    Failed(String),
+    Panicked(String),
+}
+
+/// What `EventQueue::drain` found.
+pub enum Drained {
+    Events(Vec<EventPayload>),
+    Ended(Ending),
+}
+
+impl EventQueue {
+    pub fn new() -> io::Result<Self> {
+        Ok(Self {
+            events: Mutex::new(VecDeque::new()),
+            finished: Mutex::new(None),
+            signal: ReadySignal::new()?,
+        })
+    }
+
+    pub fn fd(&self) -> i32 {
+        self.signal.fd()
+    }
+
+    pub fn push(&self, event: EventPayload) {
+        lock(&self.events).push_back(event);
+        self.signal.notify();
+    }
+
+    pub fn finish(&self, ending: Ending) {
+        *lock(&self.finished) = Some(ending);
+        self.signal.notify();
+    }
+
+    /// Takes every queued event without blocking. Once the loop has ended
+    /// and everything has been taken, tells how it ended instead.
+    pub fn drain(&self) -> Drained {
+        // Cleared first, so that an event pushed while draining leaves the
+        // signal set rather than getting lost.
+        self.signal.clear();
+
+        let events: Vec<EventPayload> = lock(&self.events).drain(..).collect();
+        if !events.is_empty() {
+            return Drained::Events(events);
+        }
+        match lock(&self.finished).clone() {
+            Some(ending) => {
+                // Stays readable, so that anyone still waiting finds out.
+                self.signal.notify();
+                Drained::Ended(ending)
+            }
+            None => Drained::Events(Vec::new()),
+        }
+    }
+}
+
+// A panic while holding the lock leaves nothing half-updated here, so a
+// poisoned lock is still fine to use.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
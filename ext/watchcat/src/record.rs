@@ -0,0 +1,101 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, LineWriter, Write},
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use notify::Event;
+use serde::{Deserialize, Serialize};
+
+use crate::session::WatchOptions;
+
+// Bumped when recordings made by an older version can't be replayed.
+const FORMAT: u32 = 1;
+
+// The first line of a recording: what was watched, and how. Read by
+// `Watchcat.replay` to set up the same watch.
+#[derive(Serialize)]
+struct Header<'a> {
+    watchcat: u32,
+    paths: &'a [String],
+    options: &'a WatchOptions,
+}
+
+// Each following line: a raw event from the backend, or a change to the
+// watched paths, and when it happened, in seconds since watching started.
+#[derive(Serialize, Deserialize)]
+pub struct Record {
+    pub t: f64,
+    #[serde(flatten)]
+    pub entry: Entry,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Entry {
+    Event(Event),
+    Watch { paths: Vec<String>, recursive: bool, depth: Option<usize> },
+    Unwatch { paths: Vec<String> },
+}
+
+// Writes what the watch loop sees to the file given as `record_to`, one JSON
+// object per line.
+pub struct Recorder {
+    out: LineWriter<File>,
+    started: Instant,
+    path: PathBuf,
+    canonical: Option<PathBuf>,
+}
+
+impl Recorder {
+    pub fn create(path: &Path, paths: &[String], options: &WatchOptions) -> io::Result<Self> {
+        let mut out = LineWriter::new(File::create(path)?);
+        let header = Header { watchcat: FORMAT, paths, options };
+        serde_json::to_writer(&mut out, &header)?;
+        out.write_all(b"\n")?;
+
+        Ok(Self {
+            out,
+            started: Instant::now(),
+            path: path.to_path_buf(),
+            canonical: path.canonicalize().ok(),
+        })
+    }
+
+    // Whether `path` is the recording itself. Writing to it causes events of
+    // its own when it's in a watched directory, which mustn't be recorded
+    // (or reported) in turn.
+    pub fn is_recording(&self, path: &Path) -> bool {
+        path == self.path
+            || (path.file_name() == self.path.file_name()
+                && self.canonical.is_some()
+                && path.canonicalize().ok() == self.canonical)
+    }
+
+    // Written a line at a time, so that a recording is complete up to the
+    // last event even if the process dies.
+    pub fn record(&mut self, entry: Entry) -> io::Result<()> {
+        let record = Record {
+            t: self.started.elapsed().as_secs_f64(),
+            entry,
+        };
+        serde_json::to_writer(&mut self.out, &record)?;
+        self.out.write_all(b"\n")
+    }
+}
+
+// Reads the records of a recording, skipping the header.
+pub fn read(path: &Path) -> Result<Vec<Record>, String> {
+    let file = File::open(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    BufReader::new(file)
+        .lines()
+        .enumerate()
+        .skip(1)
+        .filter(|(_, line)| line.as_ref().map_or(true, |line| !line.trim().is_empty()))
+        .map(|(i, line)| {
+            let line = line.map_err(|e| format!("{}: {e}", path.display()))?;
+            serde_json::from_str(&line).map_err(|e| format!("{}:{}: {e}", path.display(), i + 1))
+        })
+        .collect()
+}
@@ -0,0 +1,99 @@
+use std::path::{Path, PathBuf};
+
+// A path registered with the watcher. Backends disagree on which form they
+// report event paths in (inotify and polling echo back whatever was
+// registered, FSEvents always reports the canonical absolute path), so the
+// canonical form is kept alongside the path as given.
+#[derive(Debug)]
+struct WatchRoot {
+    name: String,
+    path: PathBuf,
+    canonical: Option<PathBuf>,
+    // How many levels below the root events are reported for, if limited.
+    depth: Option<usize>,
+}
+
+impl WatchRoot {
+    fn prefix_len(&self) -> usize {
+        self.canonical.as_ref().unwrap_or(&self.path).components().count()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct WatchRoots {
+    roots: Vec<WatchRoot>,
+}
+
+impl WatchRoots {
+    pub fn add(&mut self, name: &str, depth: Option<usize>) {
+        self.add_at(name, Path::new(name), depth);
+    }
+
+    /// Like `add`, for a root that's at `location` rather than where its name
+    /// points now, like a relative one once the current directory changed.
+    pub fn add_at(&mut self, name: &str, location: &Path, depth: Option<usize>) {
+        if self.roots.iter().any(|r| r.name == name) {
+            return;
+        }
+
+        let path = PathBuf::from(name);
+        let canonical = location.canonicalize().ok().filter(|c| *c != path);
+        self.roots.push(WatchRoot {
+            name: name.to_string(),
+            path,
+            canonical,
+            depth,
+        });
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.roots.retain(|r| r.name != name);
+    }
+
+    /// Returns the registered root that `path` belongs to and `path` relative
+    /// to it. When roots overlap, the longest (most specific) one wins. An
+    /// event on the root itself is reported relative as `"."`.
+    pub fn resolve(&self, path: &Path) -> Option<(&str, PathBuf)> {
+        self.find(path).map(|(root, relative)| {
+            let relative = if relative.as_os_str().is_empty() {
+                PathBuf::from(".")
+            } else {
+                relative.to_path_buf()
+            };
+            (root.name.as_str(), relative)
+        })
+    }
+
+    /// Whether `path` is within the `depth` limit of the root it belongs to.
+    /// Paths outside every root, and roots without a limit, always are.
+    pub fn within_depth(&self, path: &Path) -> bool {
+        self.remaining_depth(path) != Some(None)
+    }
+
+    /// How many more levels below `path` are within the depth limit of its
+    /// root: `None` if the root isn't limited, `Some(None)` if `path` itself
+    /// is already too deep.
+    pub fn remaining_depth(&self, path: &Path) -> Option<Option<usize>> {
+        let (root, relative) = self.find(path)?;
+        let limit = root.depth?;
+        Some(limit.checked_sub(relative.components().count()))
+    }
+
+    fn find<'a>(&self, path: &'a Path) -> Option<(&WatchRoot, &'a Path)> {
+        let mut best: Option<(&WatchRoot, &'a Path)> = None;
+
+        for root in &self.roots {
+            let relative = std::iter::once(&root.path)
+                .chain(root.canonical.iter())
+                .find_map(|base| path.strip_prefix(base).ok());
+
+            if let Some(relative) = relative {
+                if best.is_none_or(|(b, _)| root.prefix_len() > b.prefix_len()) {
+                    best = Some((root, relative));
+                }
+            }
+        }
+
+        best
+    }
+}
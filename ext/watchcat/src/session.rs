@@ -0,0 +1,958 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use notify::{
+    event::{CreateKind, MetadataKind, ModifyKind, RenameMode},
+    Config, Event, EventKind, NullWatcher, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher,
+};
+use serde::Serialize;
+use walkdir::WalkDir;
+
+use crate::atomic::AtomicSaves;
+use crate::content::{ContentTracker, TrackContent};
+use crate::event::{process_name, EventAttributes, FileStat, EventDetails, EventPayload, WatchatEvent};
+use crate::fanotify::FanotifyWatcher;
+use crate::filter;
+use crate::logging::{self, log};
+use crate::normalize::{Change, ChangeKind, Normalizer};
+use crate::record::{Entry, Recorder};
+use crate::roots::WatchRoots;
+use crate::symlinks::SymlinkMap;
+use crate::tail::Tail;
+
+// FSEvents and ReadDirectoryChangesW never descend into symlinked
+// directories, so `follow_symlinks: true` is emulated on those backends by
+// watching each link target separately.
+const EMULATE_FOLLOW_SYMLINKS: bool = cfg!(any(target_os = "macos", target_os = "windows"));
+
+#[derive(Debug)]
+enum WatcherEnum {
+    Poll(PollWatcher),
+    Recommended(RecommendedWatcher),
+    // `backend: :fake`: watches nothing, events are injected.
+    Fake(NullWatcher),
+    Fanotify(FanotifyWatcher),
+}
+
+impl WatcherEnum {
+    fn name(&self) -> &'static str {
+        match self {
+            WatcherEnum::Poll(_) => "poll",
+            WatcherEnum::Recommended(_) => "native",
+            WatcherEnum::Fake(_) => "fake",
+            WatcherEnum::Fanotify(_) => "fanotify",
+        }
+    }
+}
+
+fn watcher_watch(w: &mut WatcherEnum, path: &Path, mode: RecursiveMode) -> notify::Result<()> {
+    match w {
+        WatcherEnum::Poll(x) => x.watch(path, mode),
+        WatcherEnum::Recommended(x) => x.watch(path, mode),
+        WatcherEnum::Fake(x) => x.watch(path, mode),
+        WatcherEnum::Fanotify(x) => x.watch(path, mode),
+    }
+}
+
+fn watcher_unwatch(w: &mut WatcherEnum, path: &Path) -> notify::Result<()> {
+    match w {
+        WatcherEnum::Poll(x) => x.unwatch(path),
+        WatcherEnum::Recommended(x) => x.unwatch(path),
+        WatcherEnum::Fake(x) => x.unwatch(path),
+        WatcherEnum::Fanotify(x) => x.unwatch(path),
+    }
+}
+
+// Watches a root given to `watch`/`add`. Returns the subdirectories that had
+// to be watched separately to honor a depth limit.
+fn watch_root(
+    w: &mut WatcherEnum,
+    path: &Path,
+    recursive: bool,
+    depth: Option<usize>,
+    follow_symlinks: bool,
+) -> notify::Result<Vec<PathBuf>> {
+    match depth {
+        Some(depth) if recursive => watch_levels(w, path, depth, follow_symlinks),
+        _ => {
+            let mode = if recursive { RecursiveMode::Recursive } else { RecursiveMode::NonRecursive };
+            watcher_watch(w, path, mode).map(|_| Vec::new())
+        }
+    }
+}
+
+// Emulates a depth limit on top of notify's all-or-nothing `RecursiveMode`:
+// `dir` is watched non-recursively, and so is every directory below it whose
+// entries are still within `levels` levels of `dir`. Returns the directories
+// watched besides `dir` itself.
+fn watch_levels(
+    w: &mut WatcherEnum,
+    dir: &Path,
+    levels: usize,
+    follow_symlinks: bool,
+) -> notify::Result<Vec<PathBuf>> {
+    watcher_watch(w, dir, RecursiveMode::NonRecursive)?;
+
+    let mut watched = Vec::new();
+    if levels < 2 {
+        return Ok(watched);
+    }
+    let walker = WalkDir::new(dir)
+        .min_depth(1)
+        .max_depth(levels - 1)
+        .follow_links(follow_symlinks);
+    for entry in walker.into_iter().filter_map(Result::ok) {
+        if entry.file_type().is_dir()
+            && watcher_watch(w, entry.path(), RecursiveMode::NonRecursive).is_ok()
+        {
+            watched.push(entry.into_path());
+        }
+    }
+    Ok(watched)
+}
+
+fn watch_symlink_targets(
+    w: &mut WatcherEnum,
+    roots: &WatchRoots,
+    emulated_targets: &mut Vec<PathBuf>,
+    targets: Vec<PathBuf>,
+    mode: RecursiveMode,
+) {
+    for target in targets {
+        // Already delivered through the watch on the root it lives in.
+        if roots.resolve(&target).is_some() || emulated_targets.contains(&target) {
+            continue;
+        }
+        if watcher_watch(w, &target, mode).is_ok() {
+            emulated_targets.push(target);
+        }
+    }
+}
+
+// `root_removed` or `root_restored`, which bypass `atomic_saves` and
+// `normalize`.
+fn root_event(root: &str, info: &str) -> Event {
+    Event::new(EventKind::Other).add_path(PathBuf::from(root)).set_info(info)
+}
+
+fn nearest_ancestor(path: &Path) -> Option<PathBuf> {
+    path.ancestors().skip(1).find(|a| a.is_dir()).map(Path::to_path_buf)
+}
+
+// Some backends (inotify) report events under a relative root with absolute
+// paths.
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+// The events for a root that appeared with `allow_missing`: its creation, and
+// that of everything in it that would have been reported, parents first.
+fn created(root: &Path, recursive: bool, depth: Option<usize>, follow_symlinks: bool) -> Vec<Event> {
+    let max_depth = if recursive { depth.unwrap_or(usize::MAX) } else { 1 };
+    WalkDir::new(root)
+        .max_depth(max_depth)
+        .follow_links(follow_symlinks)
+        .into_iter()
+        .filter_map(Result::ok)
+        .map(|entry| {
+            let kind = if entry.file_type().is_dir() { CreateKind::Folder } else { CreateKind::File };
+            Event::new(EventKind::Create(kind)).add_path(entry.into_path())
+        })
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    // The platform's backend, or polling with `force_polling`.
+    Native,
+    // Reports nothing but the events given to `Watchcat::Testing.inject`.
+    Fake,
+    // inotify, with writes reported by fanotify to tell the process behind
+    // them. The native backend where fanotify can't be used.
+    Fanotify,
+}
+
+// Options given to `Watchcat::Watcher#watch`. Written to the header of a
+// recording, without the ones that don't affect the events.
+#[derive(Serialize)]
+pub struct WatchOptions {
+    #[serde(skip)]
+    pub backend: Backend,
+    // Where to record the raw events to, for `Watchcat.replay`.
+    #[serde(skip)]
+    pub record_to: Option<PathBuf>,
+    pub recursive: bool,
+    pub force_polling: bool,
+    pub poll_interval: u64,
+    // `None` leaves the backend default (follow) and skips resolving links.
+    pub follow_symlinks: Option<bool>,
+    // Levels below each root to report events for. Only for recursive watches.
+    pub depth: Option<usize>,
+    pub atomic_saves: bool,
+    pub normalize: bool,
+    // Whether to `lstat` the paths of each event (`Watchcat::Event#stat`).
+    pub stat: bool,
+    pub track_content: Option<TrackContent>,
+    // The file `Watchcat.tail` follows, and the offset to start at (`None`
+    // for the end).
+    #[serde(skip)]
+    pub tail: Option<(PathBuf, Option<u64>)>,
+    // Whether paths that don't exist are waited for rather than an error.
+    pub allow_missing: bool,
+    pub ignore_remove: bool,
+    pub ignore_access: bool,
+    pub ignore_create: bool,
+    pub ignore_modify: bool,
+    pub ignore_hidden: bool,
+    pub ignore_editor_temp: bool,
+}
+
+impl WatchOptions {
+    fn root_depth(&self) -> Option<usize> {
+        self.depth.filter(|_| self.recursive)
+    }
+
+    fn follow_links(&self) -> bool {
+        self.follow_symlinks.unwrap_or(true)
+    }
+
+    // Whether the `ignore_*` kind options rule out `event`.
+    fn ignores_kind(&self, event: &Event) -> bool {
+        if self.ignore_remove && matches!(event.kind, EventKind::Remove(_)) {
+            return true;
+        }
+
+        let macos_ambiguous_metadata_touch = cfg!(target_os = "macos")
+            && matches!(
+                event.kind,
+                EventKind::Modify(ModifyKind::Metadata(MetadataKind::Any))
+            );
+        if self.ignore_access
+            && (matches!(event.kind, EventKind::Access(_)) || macos_ambiguous_metadata_touch)
+        {
+            return true;
+        }
+        if self.ignore_create && matches!(event.kind, EventKind::Create(_)) {
+            return true;
+        }
+        if self.ignore_modify && matches!(event.kind, EventKind::Modify(_)) {
+            return true;
+        }
+        false
+    }
+
+    // Whether `ignore_hidden`/`ignore_editor_temp` rule out `path`.
+    fn ignores_path(&self, roots: &WatchRoots, path: &Path) -> bool {
+        if self.ignore_editor_temp && filter::is_editor_temp(path) {
+            return true;
+        }
+        if self.ignore_hidden {
+            let hidden = match roots.resolve(path) {
+                Some((_, relative)) => filter::is_hidden(&relative),
+                None => path.file_name().is_some_and(|name| filter::is_hidden(Path::new(name))),
+            };
+            if hidden {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+// An event ready to be reported.
+pub struct Report {
+    pub event: Event,
+    // The change and whether it is to a directory, in `normalize` mode.
+    pub normalized: Option<(ChangeKind, bool)>,
+}
+
+impl From<Event> for Report {
+    fn from(event: Event) -> Self {
+        Self { event, normalized: None }
+    }
+}
+
+impl From<Change> for Report {
+    fn from(change: Change) -> Self {
+        Self {
+            event: change.to_event(),
+            normalized: Some((change.kind, change.directory)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Command {
+    Watch(Vec<String>, bool, Option<usize>),   // paths, recursive, depth
+    Unwatch(Vec<String>),       // paths
+    // An event for `backend: :fake`, and where to signal once it has been
+    // delivered.
+    Inject(Event, crossbeam_channel::Sender<()>),
+}
+
+// Everything the watch loop keeps between events: the notify watcher and
+// what is known about the paths it watches.
+pub struct WatchSession {
+    watcher: WatcherEnum,
+    options: WatchOptions,
+    roots: WatchRoots,
+    symlinks: SymlinkMap,
+    // Link targets watched separately to emulate `follow_symlinks`.
+    emulated_targets: Vec<PathBuf>,
+    emulate_symlinks: bool,
+    // Directories watched one by one to honor `depth`, by root.
+    depth_dirs: HashMap<String, Vec<PathBuf>>,
+    atomic_saves: Option<AtomicSaves>,
+    normalizer: Option<Normalizer>,
+    recorder: Option<Recorder>,
+    contents: Option<ContentTracker>,
+    tail: Option<Tail>,
+    // The roots being watched, and how (`recursive`, `depth`).
+    watched: HashMap<String, (bool, Option<usize>)>,
+    // The roots that don't exist (yet, with `allow_missing`, or any more),
+    // and the ancestors watched for them.
+    missing: Vec<MissingRoot>,
+    ancestors: HashSet<PathBuf>,
+    // Where each root is, as an absolute path resolved when it was given, so
+    // that a relative one is still found after the current directory changed.
+    locations: HashMap<String, PathBuf>,
+}
+
+// A root that doesn't exist. Its nearest existing ancestor is watched
+// instead until it appears.
+struct MissingRoot {
+    name: String,
+    // Its location.
+    path: PathBuf,
+    recursive: bool,
+    depth: Option<usize>,
+    ancestor: PathBuf,
+    // Whether it was watched before, and reported as `root_removed`.
+    removed: bool,
+}
+
+impl MissingRoot {
+    // Whether an event on `path` may be about the root appearing: it's at or
+    // below the root, or in a directory the root is below.
+    fn relates_to(&self, path: &Path) -> bool {
+        let path = absolute(path);
+        path.starts_with(&self.path) || path.parent().is_some_and(|parent| self.path.starts_with(parent))
+    }
+}
+
+impl WatchSession {
+    pub fn new(
+        pathnames: &[String],
+        options: WatchOptions,
+        tx: crossbeam_channel::Sender<notify::Result<Event>>,
+    ) -> notify::Result<Self> {
+        let mut config = Config::default();
+        if let Some(follow_symlinks) = options.follow_symlinks {
+            config = config.with_follow_symlinks(follow_symlinks);
+        }
+
+        let watcher = match (options.backend, options.force_polling) {
+            (Backend::Fake, _) => WatcherEnum::Fake(NullWatcher),
+            (Backend::Native, true) => {
+                let delay = Duration::from_millis(options.poll_interval);
+                // Polling only keeps mtimes to the second, so a write within
+                // the same second as the last one would be missed without
+                // comparing contents. That matters most with `normalize`,
+                // which promises the same changes as the native backend.
+                let config = config
+                    .with_poll_interval(delay)
+                    .with_compare_contents(options.normalize);
+                WatcherEnum::Poll(PollWatcher::new(tx, config)?)
+            }
+            (Backend::Native, false) => WatcherEnum::Recommended(RecommendedWatcher::new(tx, config)?),
+            // `force_polling` is rejected with fanotify by `parse_args`.
+            (Backend::Fanotify, _) => match FanotifyWatcher::new(tx.clone(), config) {
+                Ok(watcher) => WatcherEnum::Fanotify(watcher),
+                Err(e) => {
+                    log!(Warn, "fanotify unavailable, using the native backend", error = e);
+                    WatcherEnum::Recommended(RecommendedWatcher::new(tx, config)?)
+                }
+            },
+        };
+        let emulate_symlinks = options.follow_symlinks == Some(true)
+            && EMULATE_FOLLOW_SYMLINKS
+            && matches!(watcher, WatcherEnum::Recommended(_) | WatcherEnum::Fanotify(_));
+        let atomic_saves = options.atomic_saves.then(AtomicSaves::default);
+        let normalizer = options.normalize.then(|| Normalizer::new(options.follow_links()));
+        let contents = options.track_content.clone().map(ContentTracker::new);
+        let tail = options.tail.clone().map(|(path, offset)| Tail::new(path, offset));
+        let recorder = match &options.record_to {
+            Some(path) => Some(Recorder::create(path, pathnames, &options).map_err(notify::Error::io)?),
+            None => None,
+        };
+
+        let mut session = Self {
+            watcher,
+            options,
+            roots: WatchRoots::default(),
+            symlinks: SymlinkMap::default(),
+            emulated_targets: Vec::new(),
+            emulate_symlinks,
+            depth_dirs: HashMap::new(),
+            atomic_saves,
+            normalizer,
+            recorder,
+            contents,
+            tail,
+            watched: HashMap::new(),
+            missing: Vec::new(),
+            ancestors: HashSet::new(),
+            locations: HashMap::new(),
+        };
+        for pathname in pathnames {
+            session.locate(pathname);
+        }
+        let (pathnames, missing): (Vec<_>, Vec<_>) = pathnames
+            .iter()
+            .partition(|p| !session.options.allow_missing || Path::new(p).exists());
+        for pathname in &pathnames {
+            session.roots.add(pathname, session.options.root_depth());
+        }
+        for pathname in &pathnames {
+            let dirs = watch_root(
+                &mut session.watcher,
+                Path::new(pathname),
+                session.options.recursive,
+                session.options.depth,
+                session.options.follow_links(),
+            )
+            .inspect_err(|e| log!(Error, "failed to watch", path = pathname, error = e))?;
+            log!(
+                Info,
+                "watching",
+                path = pathname,
+                backend = session.watcher.name(),
+                recursive = session.options.recursive,
+                depth = format!("{:?}", session.options.root_depth()),
+            );
+            session.depth_dirs.insert(pathname.to_string(), dirs);
+            session
+                .watched
+                .insert(pathname.to_string(), (session.options.recursive, session.options.depth));
+        }
+        for pathname in &pathnames {
+            session.scan_symlinks(Path::new(pathname), session.options.recursive);
+            session.scan_normalizer(pathname, session.options.recursive, session.options.depth);
+            session.scan_contents(Path::new(pathname), session.options.recursive, session.options.depth);
+        }
+        for pathname in missing {
+            // Nothing can be reported yet, so one that appeared meanwhile is
+            // just watched.
+            if session.wait_for_root(pathname, session.options.recursive, session.options.depth, false) {
+                session.watch_root(pathname, Path::new(pathname), session.options.recursive, session.options.depth)?;
+                session.scan_normalizer(pathname, session.options.recursive, session.options.depth);
+            }
+        }
+
+        Ok(session)
+    }
+
+    pub fn handle(&mut self, cmd: Command) {
+        match &cmd {
+            Command::Watch(paths, recursive, depth) => self.record(Entry::Watch {
+                paths: paths.clone(),
+                recursive: *recursive,
+                depth: *depth,
+            }),
+            Command::Unwatch(paths) => self.record(Entry::Unwatch { paths: paths.clone() }),
+            Command::Inject(..) => {}
+        }
+
+        match cmd {
+            Command::Watch(paths, recursive, depth) => {
+                for p in &paths {
+                    self.locate(p);
+                    if self.options.allow_missing && !Path::new(p).exists() && !self.wait_for_root(p, recursive, depth, false) {
+                        continue;
+                    }
+                    match self.watch_root(p, Path::new(p), recursive, depth) {
+                        Ok(()) => self.scan_normalizer(p, recursive, depth),
+                        Err(e) => log!(Warn, "failed to watch", path = p, error = e),
+                    }
+                }
+            }
+            // Handled by `inject`, which returns what to report.
+            Command::Inject(..) => {}
+            Command::Unwatch(paths) => {
+                for p in &paths {
+                    log!(Info, "unwatching", path = p);
+                    if let Some(i) = self.missing.iter().position(|m| m.name == *p) {
+                        let missing = self.missing.remove(i);
+                        self.release_ancestor(&missing.ancestor);
+                    } else {
+                        self.unwatch_root(p);
+                    }
+                    self.roots.remove(p);
+                    self.locations.remove(p);
+                    if let Some(normalizer) = self.normalizer.as_mut() {
+                        normalizer.forget(Path::new(p));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Takes an event from the watcher and returns the events to report for
+    /// it, which may be none (filtered out or held back) or several
+    /// (released by `atomic_saves` or `normalize`).
+    pub fn process(&mut self, event: Event) -> Vec<Report> {
+        log!(Debug, "event", kind = format!("{:?}", event.kind), paths = logging::paths(&event.paths));
+
+        if let Some(recorder) = self.recorder.as_ref() {
+            if !event.paths.is_empty() && event.paths.iter().all(|p| recorder.is_recording(p)) {
+                log!(Debug, "event dropped", reason = "recording", paths = logging::paths(&event.paths));
+                return Vec::new();
+            }
+            self.record(Entry::Event(event.clone()));
+        }
+
+        let (kind, paths) = (event.kind, event.paths.clone());
+        let mut reports = Vec::new();
+        if self.missing.iter().any(|m| paths.iter().any(|p| m.relates_to(p))) {
+            // Only there because an ancestor of a missing root is watched.
+            let only_ancestors = !paths.is_empty()
+                && paths.iter().all(|p| {
+                    !self.within_watched(p) && self.missing.iter().any(|m| m.relates_to(p))
+                });
+            reports.extend(self.appeared());
+            if only_ancestors {
+                log!(Debug, "event dropped", reason = "missing", paths = logging::paths(&paths));
+                return reports;
+            }
+        }
+        reports.extend(self.pipeline(event));
+        reports.extend(self.removed(kind, &paths));
+        reports
+    }
+
+    // The rest of `process`: the filters, `atomic_saves` and `normalize`.
+    fn pipeline(&mut self, mut event: Event) -> Vec<Report> {
+        if !event.paths.is_empty() && !event.paths.iter().any(|p| self.roots.within_depth(p)) {
+            log!(Debug, "event dropped", reason = "depth", paths = logging::paths(&event.paths));
+            return Vec::new();
+        }
+
+        self.track(&event);
+
+        // With emulated following, events under a target arrive with the
+        // target path; report them under the link instead.
+        for p in event.paths.iter_mut() {
+            if self.emulated_targets.iter().any(|t| p.starts_with(t)) {
+                if let Some(link) = self.symlinks.to_link(p) {
+                    *p = link;
+                }
+            }
+        }
+
+        // Before `atomic_saves`, `normalize` and the filters, so that the
+        // contents are current even for changes that aren't reported.
+        if let Some(contents) = self.contents.as_mut() {
+            let roots = &self.roots;
+            contents.update(&event, |p| roots.resolve(p).map(|(_, relative)| relative));
+        }
+
+        let now = Instant::now();
+        let events = match self.atomic_saves.as_mut() {
+            Some(atomic_saves) => atomic_saves.process(event, now),
+            None => vec![event],
+        };
+        let reports = self.normalize(events, now);
+        self.accept(reports)
+    }
+
+    /// Like `process`, for an event made up by `Watchcat::Testing.inject`.
+    /// Only the fake backend takes them, as a real one would mix them up with
+    /// actual changes.
+    pub fn inject(&mut self, event: Event) -> Vec<Report> {
+        match self.watcher {
+            WatcherEnum::Fake(_) => self.process(event),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Returns the held-back events whose time is up.
+    pub fn flush(&mut self) -> Vec<Report> {
+        let now = Instant::now();
+        let events = match self.atomic_saves.as_mut() {
+            Some(atomic_saves) => atomic_saves.expire(now),
+            None => Vec::new(),
+        };
+        let reports = self.normalize(events, now);
+        self.accept(reports)
+    }
+
+    /// When `flush` should be called next, if anything is held back.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        let atomic_saves = self.atomic_saves.as_ref().and_then(AtomicSaves::next_deadline);
+        let normalizer = self.normalizer.as_ref().and_then(Normalizer::next_deadline);
+        atomic_saves.into_iter().chain(normalizer).min()
+    }
+
+    pub fn describe(&mut self, report: Report) -> EventPayload {
+        let event = &report.event;
+        let paths = event
+            .paths
+            .iter()
+            .map(|p| p.to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+
+        let details = EventDetails {
+            root: event
+                .paths
+                .first()
+                .and_then(|p| self.roots.resolve(p))
+                .map(|(root, _)| root.to_string()),
+            relative_paths: event
+                .paths
+                .iter()
+                .map(|p| {
+                    self.roots
+                        .resolve(p)
+                        .map(|(_, rel)| rel.to_string_lossy().into_owned())
+                })
+                .collect(),
+            resolved_paths: event
+                .paths
+                .iter()
+                .map(|p| {
+                    self.symlinks
+                        .resolve(p)
+                        .unwrap_or_else(|| p.clone())
+                        .to_string_lossy()
+                        .into_owned()
+                })
+                .collect(),
+            attributes: EventAttributes::from(event),
+            process_name: event.attrs.process_id().and_then(process_name),
+            diff: self.contents.as_mut().and_then(|contents| contents.take(event)),
+            appended: self.tail.as_mut().and_then(|tail| tail.update(event)),
+            // At delivery rather than when the backend reported the event,
+            // which `atomic_saves` and `normalize` may have held back.
+            stats: self
+                .options
+                .stat
+                .then(|| event.paths.iter().map(|p| FileStat::of(p)).collect()),
+            normalized: report
+                .normalized
+                .map(|(kind, directory)| (kind.name().to_string(), directory)),
+            time: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0.0, |d| d.as_secs_f64()),
+        };
+
+        EventPayload {
+            kinds: WatchatEvent::convert_kind(&event.kind),
+            paths,
+            raw_kind: format!("{:?}", event.kind),
+            details,
+        }
+    }
+
+    // Recording is best effort: it stops at the first failure to write, but
+    // watching goes on.
+    fn record(&mut self, entry: Entry) {
+        if let Some(recorder) = self.recorder.as_mut() {
+            if let Err(e) = recorder.record(entry) {
+                log!(Warn, "recording stopped", error = e);
+                self.recorder = None;
+            }
+        }
+    }
+
+    // Keeps the watches up to date with directories and links that appeared.
+    fn track(&mut self, event: &Event) {
+        // A directory that appeared within the depth limit needs watches of
+        // its own.
+        if matches!(
+            event.kind,
+            EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(RenameMode::To | RenameMode::Both))
+        ) {
+            if let Some(p) = event.paths.last() {
+                if let (Some(Some(levels)), Some((root, _))) = (self.roots.remaining_depth(p), self.roots.resolve(p)) {
+                    if levels > 0 && p.is_dir() {
+                        if let Ok(dirs) = watch_levels(&mut self.watcher, p, levels, self.options.follow_links()) {
+                            let watched = self.depth_dirs.entry(root.to_string()).or_default();
+                            watched.push(p.clone());
+                            watched.extend(dirs);
+                        }
+                    }
+                }
+            }
+        }
+
+        // A symlinked directory created after the watch started.
+        if matches!(event.kind, EventKind::Create(_)) {
+            for p in &event.paths {
+                if p.symlink_metadata().is_ok_and(|m| m.file_type().is_symlink()) {
+                    self.scan_symlinks(p, self.options.recursive);
+                }
+            }
+        }
+    }
+
+    fn scan_normalizer(&mut self, name: &str, recursive: bool, depth: Option<usize>) {
+        let location = self.location(name);
+        if let Some(normalizer) = self.normalizer.as_mut() {
+            let max_depth = if recursive { depth } else { Some(1) };
+            normalizer.scan(Path::new(name), &location, max_depth);
+        }
+    }
+
+    // Watches a root that exists at `path`, and starts keeping track of it.
+    // The normalizer is left to the caller: a root that was missing is
+    // scanned before it exists, so that what's in it is reported as created.
+    fn watch_root(&mut self, name: &str, path: &Path, recursive: bool, depth: Option<usize>) -> notify::Result<()> {
+        let dirs = watch_root(&mut self.watcher, path, recursive, depth, self.options.follow_links())?;
+        log!(Info, "watching", path = name, recursive = recursive, depth = format!("{:?}", depth));
+        // Again for a root that was missing, as its canonical form is only
+        // known now.
+        self.roots.remove(name);
+        self.roots.add_at(name, path, depth.filter(|_| recursive));
+        self.depth_dirs.insert(name.to_string(), dirs);
+        self.watched.insert(name.to_string(), (recursive, depth));
+        self.scan_symlinks(path, recursive);
+        self.scan_contents(path, recursive, depth);
+        Ok(())
+    }
+
+    fn locate(&mut self, name: &str) {
+        self.locations
+            .entry(name.to_string())
+            .or_insert_with(|| absolute(Path::new(name)));
+    }
+
+    fn location(&self, name: &str) -> PathBuf {
+        self.locations
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| absolute(Path::new(name)))
+    }
+
+    // Stops watching a root, except for what is needed to report events
+    // under it that are still to come (`roots`, `normalizer`).
+    fn unwatch_root(&mut self, name: &str) {
+        self.watched.remove(name);
+        let _ = watcher_unwatch(&mut self.watcher, Path::new(name));
+        for dir in self.depth_dirs.remove(name).unwrap_or_default() {
+            let _ = watcher_unwatch(&mut self.watcher, &dir);
+        }
+        if let Some(contents) = self.contents.as_mut() {
+            contents.forget(Path::new(name));
+        }
+        for target in self.symlinks.forget(Path::new(name)) {
+            if let Some(i) = self.emulated_targets.iter().position(|t| *t == target) {
+                let _ = watcher_unwatch(&mut self.watcher, &self.emulated_targets.remove(i));
+            }
+        }
+    }
+
+    fn within_watched(&self, path: &Path) -> bool {
+        self.roots
+            .resolve(path)
+            .is_some_and(|(root, _)| self.watched.contains_key(root))
+    }
+
+    // Starts waiting for a root that doesn't exist, by watching its nearest
+    // existing ancestor. Returns `true` if the root exists after all, having
+    // been created in the meantime.
+    fn wait_for_root(&mut self, name: &str, recursive: bool, depth: Option<usize>, removed: bool) -> bool {
+        let path = self.location(name);
+        let Some(ancestor) = nearest_ancestor(&path) else {
+            log!(Warn, "no existing ancestor to wait for path in", path = name);
+            return false;
+        };
+        // Events in an ancestor within a root are reported already.
+        if !self.ancestors.contains(&ancestor) && !self.covered(&ancestor) {
+            match watcher_watch(&mut self.watcher, &ancestor, RecursiveMode::NonRecursive) {
+                Ok(()) => {
+                    self.ancestors.insert(ancestor.clone());
+                }
+                Err(e) => log!(Warn, "failed to watch ancestor", path = ancestor.display(), error = e),
+            }
+        }
+        if path.exists() {
+            self.release_ancestor(&ancestor);
+            return true;
+        }
+
+        log!(Info, "waiting for missing path", path = name, ancestor = ancestor.display());
+        self.roots.add(name, depth.filter(|_| recursive));
+        // Nothing is in the snapshot for it, so that what appears is reported
+        // as created.
+        self.scan_normalizer(name, recursive, depth);
+        self.missing.push(MissingRoot {
+            name: name.to_string(),
+            path,
+            recursive,
+            depth,
+            ancestor,
+            removed,
+        });
+        false
+    }
+
+    // Whether `path` is watched as a root, or within a recursive one.
+    fn covered(&self, path: &Path) -> bool {
+        self.watched
+            .iter()
+            .any(|(root, (recursive, _))| path == Path::new(root) || (*recursive && path.starts_with(root)))
+    }
+
+    // Unwatches an ancestor watched for missing roots once none needs it.
+    fn release_ancestor(&mut self, ancestor: &Path) {
+        if self.missing.iter().any(|m| m.ancestor == ancestor) || !self.ancestors.remove(ancestor) {
+            return;
+        }
+        if !self.covered(ancestor) {
+            let _ = watcher_unwatch(&mut self.watcher, ancestor);
+        }
+    }
+
+    // Watches the missing roots that have appeared, and returns what to
+    // report for them: `root_restored` for the ones that were removed, and the
+    // root and everything in it created. Moves the watch for the others when
+    // their nearest existing ancestor changed.
+    fn appeared(&mut self) -> Vec<Report> {
+        let mut reports = Vec::new();
+        for missing in std::mem::take(&mut self.missing) {
+            let exists = missing.path.exists();
+            if !exists && nearest_ancestor(&missing.path).as_ref() == Some(&missing.ancestor) {
+                self.missing.push(missing);
+                continue;
+            }
+            self.release_ancestor(&missing.ancestor);
+            if !exists && !self.wait_for_root(&missing.name, missing.recursive, missing.depth, missing.removed) {
+                continue;
+            }
+            reports.extend(self.restore(&missing.name, missing.recursive, missing.depth, missing.removed));
+        }
+        reports
+    }
+
+    // Watches a root that was missing again, and returns what to report for
+    // it.
+    fn restore(&mut self, name: &str, recursive: bool, depth: Option<usize>, removed: bool) -> Vec<Report> {
+        let path = self.location(name);
+        if let Err(e) = self.watch_root(name, &path, recursive, depth) {
+            log!(Warn, "failed to watch", path = name, error = e);
+            return Vec::new();
+        }
+        let mut reports = Vec::new();
+        if removed {
+            log!(Info, "watched path restored", path = name);
+            reports.extend(self.accept(vec![root_event(name, "root_restored").into()]));
+        } else {
+            log!(Info, "missing path appeared", path = name);
+        }
+        for event in created(&path, recursive, depth, self.options.follow_links()) {
+            reports.extend(self.pipeline(event));
+        }
+        reports
+    }
+
+    // Reports `root_removed` for the roots an event of `kind` on `paths` shows
+    // were removed or moved away (the root or an ancestor), whose watch is
+    // gone with them, and waits for them to appear again. A root that's back
+    // already, like a directory removed and created again right away, is
+    // watched again and reported as restored.
+    fn removed(&mut self, kind: EventKind, paths: &[PathBuf]) -> Vec<Report> {
+        // Nothing is watched for real.
+        if matches!(self.watcher, WatcherEnum::Fake(_))
+            || !matches!(kind, EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(_)))
+        {
+            return Vec::new();
+        }
+        // A root removed and created again before the event is processed
+        // exists, but its watch is gone with the old one all the same.
+        let remove = matches!(kind, EventKind::Remove(_));
+        let gone = self
+            .watched
+            .keys()
+            .filter(|root| {
+                let root = self.location(root);
+                paths
+                    .iter()
+                    .map(|p| absolute(p))
+                    .any(|p| (remove && root == p) || (root.starts_with(&p) && !root.exists()))
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        let mut reports = Vec::new();
+        for root in gone {
+            log!(Info, "watched path removed", path = root);
+            reports.extend(self.accept(vec![root_event(&root, "root_removed").into()]));
+            let (recursive, depth) = self.watched[&root];
+            self.unwatch_root(&root);
+            if self.wait_for_root(&root, recursive, depth, true) {
+                reports.extend(self.restore(&root, recursive, depth, true));
+            }
+        }
+        reports
+    }
+
+    fn scan_contents(&mut self, path: &Path, recursive: bool, depth: Option<usize>) {
+        let follow_links = self.options.follow_links();
+        if let Some(contents) = self.contents.as_mut() {
+            let max_depth = if recursive { depth } else { Some(1) };
+            contents.scan(path, max_depth, follow_links);
+        }
+    }
+
+    fn normalize(&mut self, events: Vec<Event>, now: Instant) -> Vec<Report> {
+        let Some(normalizer) = self.normalizer.as_mut() else {
+            return events.into_iter().map(Report::from).collect();
+        };
+        let mut changes = Vec::new();
+        for event in &events {
+            changes.extend(normalizer.process(event, now));
+        }
+        changes.extend(normalizer.expire(now));
+        changes.into_iter().map(Report::from).collect()
+    }
+
+    fn scan_symlinks(&mut self, path: &Path, recursive: bool) {
+        if self.options.follow_symlinks != Some(true) {
+            return;
+        }
+        let targets = self.symlinks.scan(path, recursive);
+        if self.emulate_symlinks {
+            let mode = if recursive { RecursiveMode::Recursive } else { RecursiveMode::NonRecursive };
+            watch_symlink_targets(&mut self.watcher, &self.roots, &mut self.emulated_targets, targets, mode);
+        }
+    }
+
+    // Applies the `ignore_*` options.
+    fn accept(&mut self, reports: Vec<Report>) -> Vec<Report> {
+        let (accepted, dropped): (Vec<_>, Vec<_>) = reports.into_iter().partition(|report| self.accepts(report));
+        if let Some(contents) = self.contents.as_mut() {
+            for report in &dropped {
+                contents.discard(&report.event);
+            }
+        }
+        accepted
+    }
+
+    fn accepts(&self, report: &Report) -> bool {
+        let event = &report.event;
+        if self.options.ignores_kind(event) {
+            log!(Debug, "event dropped", reason = "kind", kind = format!("{:?}", event.kind), paths = logging::paths(&event.paths));
+            return false;
+        }
+        // Only when every path is ignored, so that e.g. renaming a temp file
+        // over the real one still comes through.
+        let paths = &event.paths;
+        if !paths.is_empty() && paths.iter().all(|p| self.options.ignores_path(&self.roots, p)) {
+            log!(Debug, "event dropped", reason = "path", kind = format!("{:?}", event.kind), paths = logging::paths(paths));
+            return false;
+        }
+        true
+    }
+}
@@ -0,0 +1,81 @@
+use std::io;
+
+/// A file descriptor that is readable while `notify` has been called since
+/// the last `clear`. An eventfd on Linux, a pipe on other Unix systems.
+#[derive(Debug)]
+pub struct ReadySignal {
+    read_fd: i32,
+    write_fd: i32,
+}
+
+impl ReadySignal {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn new() -> io::Result<Self> {
+        let fd = unsafe { libc::eventfd(0, libc::EFD_NONBLOCK | libc::EFD_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self { read_fd: fd, write_fd: fd })
+    }
+
+    #[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+    pub fn new() -> io::Result<Self> {
+        let mut fds = [0; 2];
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let signal = Self { read_fd: fds[0], write_fd: fds[1] };
+        for fd in fds {
+            let ok = unsafe {
+                libc::fcntl(fd, libc::F_SETFL, libc::fcntl(fd, libc::F_GETFL) | libc::O_NONBLOCK) >= 0
+                    && libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) >= 0
+            };
+            if !ok {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(signal)
+    }
+
+    #[cfg(not(unix))]
+    pub fn new() -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "readiness signalling is not supported on this platform",
+        ))
+    }
+
+    pub fn fd(&self) -> i32 {
+        self.read_fd
+    }
+
+    pub fn notify(&self) {
+        // An eventfd takes exactly 8 bytes. A full pipe (EAGAIN) is already
+        // readable, so the result doesn't matter either way.
+        #[cfg(unix)]
+        unsafe {
+            let buf = 1u64.to_ne_bytes();
+            libc::write(self.write_fd, buf.as_ptr().cast(), buf.len());
+        }
+    }
+
+    pub fn clear(&self) {
+        #[cfg(unix)]
+        unsafe {
+            let mut buf = [0u8; 512];
+            while libc::read(self.read_fd, buf.as_mut_ptr().cast(), buf.len()) > 0 {}
+        }
+    }
+}
+
+impl Drop for ReadySignal {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        unsafe {
+            libc::close(self.read_fd);
+            if self.write_fd != self.read_fd {
+                libc::close(self.write_fd);
+            }
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use std::path::{Path, PathBuf};
+
+use walkdir::WalkDir;
+
+// Symlinked directories found under the watched roots, mapped to the
+// directory they point at. Used to report the resolved target path next to
+// the link path, and to emulate `follow_symlinks` on backends that never
+// descend into symlinked directories by themselves.
+#[derive(Debug, Default)]
+pub struct SymlinkMap {
+    links: Vec<(PathBuf, PathBuf)>,
+}
+
+impl SymlinkMap {
+    /// Records every symlinked directory under `root` (including `root`
+    /// itself). Returns the newly found targets. Loops are detected by
+    /// walkdir, which reports them as errors that are simply skipped here.
+    pub fn scan(&mut self, root: &Path, recursive: bool) -> Vec<PathBuf> {
+        let walker = WalkDir::new(root).follow_links(true);
+        let walker = if recursive { walker } else { walker.max_depth(0) };
+
+        let mut found = Vec::new();
+        for entry in walker.into_iter().filter_map(Result::ok) {
+            if !entry.path_is_symlink() || !entry.file_type().is_dir() {
+                continue;
+            }
+            if self.links.iter().any(|(link, _)| link == entry.path()) {
+                continue;
+            }
+            if let Ok(target) = entry.path().canonicalize() {
+                self.links.push((entry.path().to_path_buf(), target.clone()));
+                found.push(target);
+            }
+        }
+        found
+    }
+
+    /// Forgets the links under `root`. Returns the targets that are no
+    /// longer reachable through any remaining link.
+    pub fn forget(&mut self, root: &Path) -> Vec<PathBuf> {
+        let (removed, kept): (Vec<_>, Vec<_>) = self
+            .links
+            .drain(..)
+            .partition(|(link, _)| link.starts_with(root));
+        self.links = kept;
+
+        removed
+            .into_iter()
+            .map(|(_, target)| target)
+            .filter(|target| !self.links.iter().any(|(_, t)| t == target))
+            .collect()
+    }
+
+    /// The path `path` points at, if it is under a known symlinked directory.
+    pub fn resolve(&self, path: &Path) -> Option<PathBuf> {
+        self.links
+            .iter()
+            .filter_map(|(link, target)| path.strip_prefix(link).ok().map(|rest| (link, target, rest)))
+            .max_by_key(|(link, _, _)| link.components().count())
+            .map(|(_, target, rest)| join(target, rest))
+    }
+
+    /// The reverse of `resolve`: maps a path under a link target back to the
+    /// link it was reached through.
+    pub fn to_link(&self, path: &Path) -> Option<PathBuf> {
+        self.links
+            .iter()
+            .filter_map(|(link, target)| path.strip_prefix(target).ok().map(|rest| (link, target, rest)))
+            .max_by_key(|(_, target, _)| target.components().count())
+            .map(|(link, _, rest)| join(link, rest))
+    }
+}
+
+fn join(base: &Path, rest: &Path) -> PathBuf {
+    if rest.as_os_str().is_empty() {
+        base.to_path_buf()
+    } else {
+        base.join(rest)
+    }
+}
@@ -0,0 +1,108 @@
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::PathBuf,
+};
+
+use notify::{
+    event::{AccessKind, AccessMode, ModifyKind},
+    Event, EventKind,
+};
+
+// Follows a file for `Watchcat.tail`, like `tail -F`: keeps it open, reads
+// what was appended on each event for it, and starts over on a new file at
+// the same path (log rotation) or when the file shrinks (truncation).
+pub struct Tail {
+    path: PathBuf,
+    file: Option<File>,
+    offset: u64,
+}
+
+impl Tail {
+    // Starts at `offset`, or at the end with `None`. A file shorter than
+    // `offset` is read from the start.
+    pub fn new(path: PathBuf, offset: Option<u64>) -> Self {
+        let mut tail = Self {
+            path,
+            file: None,
+            offset: 0,
+        };
+        if let Ok(file) = File::open(&tail.path) {
+            let len = file.metadata().map_or(0, |m| m.len());
+            tail.offset = offset.unwrap_or(len);
+            if tail.offset > len {
+                tail.offset = 0;
+            }
+            tail.file = Some(file);
+        }
+        tail
+    }
+
+    // What was appended since the last call, if `event` is for the file: one
+    // chunk per file read, so two when it was replaced (the rest of the old
+    // file, then the new one).
+    pub fn update(&mut self, event: &Event) -> Option<Vec<Vec<u8>>> {
+        // Reading the file causes events of its own (opening, closing and
+        // possibly the access time), which mustn't lead to reading it again.
+        let caused_by_reading = match event.kind {
+            EventKind::Access(AccessKind::Close(mode)) => mode != AccessMode::Write,
+            EventKind::Access(_) | EventKind::Modify(ModifyKind::Metadata(_)) => true,
+            _ => false,
+        };
+        if caused_by_reading || !event.paths.iter().any(|p| p == &self.path) {
+            return None;
+        }
+
+        let mut chunks = Vec::new();
+        // Written before a rotation, the rest of the old file is still there
+        // to read through the open handle.
+        chunks.extend(self.read());
+
+        match File::open(&self.path) {
+            Ok(file) if !self.file.as_ref().is_some_and(|current| same_file(current, &file)) => {
+                self.file = Some(file);
+                self.offset = 0;
+                chunks.extend(self.read());
+            }
+            Ok(_) => {}
+            Err(_) => {
+                // Gone (renamed or removed); read from the start of whatever
+                // is created at the path next.
+                self.file = None;
+                self.offset = 0;
+            }
+        }
+
+        (!chunks.is_empty()).then_some(chunks)
+    }
+
+    fn read(&mut self) -> Option<Vec<u8>> {
+        let file = self.file.as_mut()?;
+        let len = file.metadata().ok()?.len();
+        if len < self.offset {
+            // Truncated: what is there now was written since.
+            self.offset = 0;
+        }
+        file.seek(SeekFrom::Start(self.offset)).ok()?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes).ok()?;
+        self.offset += bytes.len() as u64;
+        (!bytes.is_empty()).then_some(bytes)
+    }
+}
+
+#[cfg(unix)]
+fn same_file(a: &File, b: &File) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (a.metadata(), b.metadata()) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+// Without inode numbers, a replaced file can only be told by shrinking.
+#[cfg(not(unix))]
+fn same_file(_a: &File, _b: &File) -> bool {
+    true
+}
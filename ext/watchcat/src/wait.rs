@@ -0,0 +1,196 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use crossbeam_channel::{at, never, select, unbounded, Receiver};
+use notify::{
+    event::{CreateKind, ModifyKind, RemoveKind, RenameMode},
+    Event, EventKind,
+};
+use walkdir::WalkDir;
+
+use crate::event::EventPayload;
+use crate::filter;
+use crate::session::{Backend, Report, WatchOptions, WatchSession};
+
+// What `Watchcat.wait_for` waits for.
+pub struct WaitTarget {
+    pub path: PathBuf,
+    // "create", "modify", "remove" or "access"; `None` for any kind.
+    pub kind: Option<String>,
+    // Matched against paths below `path` (a directory) instead of `path`.
+    pub pattern: Option<String>,
+}
+
+pub enum Waited {
+    Event(Box<EventPayload>),
+    TimedOut,
+    // Woken up by the unblock function so that Ruby can handle an interrupt.
+    Interrupted,
+}
+
+// A watch set up for a single `WaitTarget`. Kept across interrupts, so that
+// waiting can go on afterwards without missing anything.
+pub struct Waiter {
+    session: WatchSession,
+    rx: Receiver<notify::Result<Event>>,
+    target: WaitTarget,
+    deadline: Option<Instant>,
+    // An event that was already there before any arrived.
+    pending: Option<Report>,
+}
+
+impl Waiter {
+    pub fn new(target: WaitTarget, deadline: Option<Instant>) -> notify::Result<Self> {
+        // Without a pattern, `path` may not exist yet, so its directory is
+        // watched instead. That directory (or `path`, with a pattern) may not
+        // exist either, in which case its nearest existing ancestor is
+        // watched until it appears, as with `allow_missing`.
+        let (root, recursive) = match target.pattern {
+            Some(_) => (target.path.clone(), true),
+            None => (
+                target
+                    .path
+                    .parent()
+                    .filter(|p| !p.as_os_str().is_empty())
+                    .unwrap_or(Path::new("."))
+                    .to_path_buf(),
+                false,
+            ),
+        };
+
+        let (tx, rx) = unbounded();
+        let options = WatchOptions {
+            backend: Backend::Native,
+            record_to: None,
+            recursive,
+            force_polling: false,
+            poll_interval: 200,
+            follow_symlinks: None,
+            depth: None,
+            atomic_saves: false,
+            normalize: false,
+            stat: false,
+            track_content: None,
+            tail: None,
+            allow_missing: true,
+            ignore_remove: false,
+            ignore_access: false,
+            ignore_create: false,
+            ignore_modify: false,
+            ignore_hidden: false,
+            ignore_editor_temp: false,
+        };
+        let session = WatchSession::new(&[root.to_string_lossy().into_owned()], options, tx)?;
+
+        let mut waiter = Self {
+            session,
+            rx,
+            target,
+            deadline,
+            pending: None,
+        };
+        // Only checked once the watch is set up, so that a change right
+        // before it is either seen here or reported as an event.
+        waiter.pending = waiter.already_done().map(Report::from);
+        Ok(waiter)
+    }
+
+    pub fn wait(&mut self, cancel: &Receiver<()>) -> Result<Waited, String> {
+        if let Some(report) = self.pending.take() {
+            return Ok(Waited::Event(Box::new(self.session.describe(report))));
+        }
+
+        loop {
+            let timeout = match self.deadline {
+                Some(deadline) => at(deadline),
+                None => never(),
+            };
+
+            let event = select! {
+                recv(cancel) -> _ => return Ok(Waited::Interrupted),
+                recv(self.rx) -> res => match res {
+                    Ok(Ok(event)) => event,
+                    Ok(Err(e)) => return Err(e.to_string()),
+                    Err(e) => return Err(e.to_string()),
+                },
+                recv(timeout) -> _ => return Ok(Waited::TimedOut),
+            };
+
+            for report in self.session.process(event) {
+                let candidates = self.candidates(&report.event);
+                let payload = self.session.describe(report);
+                if candidates.into_iter().any(|i| self.matches(&payload, i)) {
+                    return Ok(Waited::Event(Box::new(payload)));
+                }
+            }
+        }
+    }
+
+    // A file that already exists when waiting for it to be created (or is
+    // already gone when waiting for it to be removed) doesn't need to wait,
+    // and is reported as an event with the info "existing" (or "missing").
+    fn already_done(&self) -> Option<Event> {
+        let (kind, info, path) = match (self.target.kind.as_deref(), &self.target.pattern) {
+            (Some("create"), None) => {
+                let metadata = self.target.path.metadata().ok()?;
+                let kind = if metadata.is_dir() { CreateKind::Folder } else { CreateKind::File };
+                (EventKind::Create(kind), "existing", self.target.path.clone())
+            }
+            (Some("create"), Some(pattern)) => {
+                let entry = WalkDir::new(&self.target.path)
+                    .min_depth(1)
+                    .into_iter()
+                    .filter_map(Result::ok)
+                    .find(|entry| {
+                        let relative = entry.path().strip_prefix(&self.target.path).unwrap_or(entry.path());
+                        filter::glob_matches(pattern, &relative.to_string_lossy())
+                    })?;
+                let kind = if entry.file_type().is_dir() { CreateKind::Folder } else { CreateKind::File };
+                (EventKind::Create(kind), "existing", entry.into_path())
+            }
+            (Some("remove"), None) if !self.target.path.exists() => {
+                (EventKind::Remove(RemoveKind::Any), "missing", self.target.path.clone())
+            }
+            _ => return None,
+        };
+
+        Some(Event::new(kind).add_path(path).set_info(info))
+    }
+
+    // The indexes of the paths of `event` that can be what is waited for. A
+    // file renamed into place counts as created, and one renamed away as
+    // removed.
+    fn candidates(&self, event: &Event) -> Vec<usize> {
+        let all = (0..event.paths.len()).collect();
+        let Some(kind) = self.target.kind.as_deref() else {
+            return all;
+        };
+
+        match (kind, &event.kind) {
+            ("create", EventKind::Create(_)) | ("remove", EventKind::Remove(_)) => all,
+            ("create", EventKind::Modify(ModifyKind::Name(RenameMode::To))) => vec![0],
+            ("create", EventKind::Modify(ModifyKind::Name(RenameMode::Both))) => vec![1],
+            ("remove", EventKind::Modify(ModifyKind::Name(RenameMode::From | RenameMode::Both))) => vec![0],
+            ("modify", EventKind::Modify(_)) | ("access", EventKind::Access(_)) => all,
+            _ => Vec::new(),
+        }
+    }
+
+    fn matches(&self, payload: &EventPayload, index: usize) -> bool {
+        let relative = payload.details.relative_paths.get(index).cloned().flatten();
+        let path = payload.paths.get(index);
+
+        match &self.target.pattern {
+            Some(pattern) => relative.is_some_and(|relative| relative != "." && filter::glob_matches(pattern, &relative)),
+            None => {
+                // Compared relative to the watched directory, as backends that
+                // report canonical paths (FSEvents) won't report `path` as given.
+                let name = self.target.path.file_name().map(|n| n.to_string_lossy().into_owned());
+                relative.is_some_and(|relative| Some(relative) == name)
+                    || path.is_some_and(|path| Path::new(path) == self.target.path)
+            }
+        }
+    }
+}
@@ -1,7 +1,12 @@
+require "json"
+require "timeout"
 require_relative "watchcat/version"
 require_relative "watchcat/executor"
+require_relative "watchcat/fork_hook"
+require_relative "watchcat/hub"
 require_relative "watchcat/debouncer"
 require_relative "watchcat/event_handler"
+require_relative "watchcat/tail"
 
 begin
   require "watchcat/#{RUBY_VERSION.to_f}/watchcat"
@@ -9,23 +14,52 @@ rescue LoadError
   require "watchcat/watchcat"
 end
 
+require_relative "watchcat/watcher"
+require_relative "watchcat/logging"
+
 module Watchcat
+  # Raised by `Watchcat.wait_for` when no matching event arrives in time.
+  class Timeout < ::Timeout::Error; end
+
+  WAIT_FOR_EVENTS = %i[create modify remove access any].freeze
+
+  # The options of `Watchcat.watch` kept in the header of a recording, which
+  # `Watchcat.replay` uses unless given.
+  REPLAYED_OPTIONS = %i[recursive follow_symlinks depth ignore_hidden ignore_editor_temp].freeze
+  # What these make of the raw events depends on when they arrive and on the
+  # filesystem at the time, so a replay wouldn't match what was reported.
+  UNREPLAYABLE_OPTIONS = %i[atomic_saves normalize].freeze
+  REPLAYED_FILTERS = %i[ignore_remove ignore_access ignore_create ignore_modify].freeze
+
   class << self
     def watch(
       paths,
       recursive: true,
       force_polling: false,
       poll_interval: nil,
+      follow_symlinks: nil,
+      depth: nil,
+      atomic_saves: false,
+      normalize: false,
+      stat: false,
+      track_content: nil,
+      allow_missing: false,
       filters: {},
       debounce: -1,
       patterns: [],
       ignore_patterns: [],
       ignore_directories: false,
+      ignore_hidden: false,
+      ignore_editor_temp: false,
       handler: nil,
+      port: nil,
+      fiber: false,
+      backend: nil,
+      record_to: nil,
       &block
     )
       callback = block || (handler && handler.method(:dispatch))
-      raise ArgumentError, "must provide a block or a handler:" unless callback
+      raise ArgumentError, "must provide a block, a handler: or a port:" unless callback || port
 
       w =
         Watchcat::Executor.new(
@@ -33,15 +67,129 @@ module Watchcat
           recursive: recursive,
           force_polling: force_polling,
           poll_interval: poll_interval,
+          follow_symlinks: follow_symlinks,
+          depth: depth,
+          atomic_saves: atomic_saves,
+          normalize: normalize,
+          stat: stat,
+          track_content: track_content,
+          allow_missing: allow_missing,
           filters: filters,
           debounce: debounce,
           patterns: patterns,
           ignore_patterns: ignore_patterns,
           ignore_directories: ignore_directories,
-          block: callback
+          ignore_hidden: ignore_hidden,
+          ignore_editor_temp: ignore_editor_temp,
+          block: callback,
+          port: port,
+          fiber: fiber,
+          backend: backend,
+          record_to: record_to && File.expand_path(record_to)
+        )
+      w.start
+      w
+    end
+
+    # Follows a growing file like `tail -F`: yields each line appended to
+    # `path` (without the newline), or each chunk with `lines: false`, and
+    # the event it came with. Starts at the end of the file, at the start with
+    # `from: :start`, or at a byte offset. A rotated (renamed and recreated)
+    # or truncated file is followed from its start, and the file doesn't have
+    # to exist yet. Returns the watcher; call `#stop` to stop following.
+    def tail(path, from: :end, lines: true, force_polling: false, poll_interval: nil, &block)
+      raise ArgumentError, "must provide a block" unless block
+
+      path = File.expand_path(path)
+      follower = Watchcat::Tail.new(lines: lines, &block)
+      offset =
+        case from
+        when :end then nil
+        when :start then follower.read_existing(path)
+        when Integer then from
+        else raise ArgumentError, "from must be :start, :end or a byte offset: #{from.inspect}"
+        end
+
+      # The directory is watched for the file being renamed and recreated.
+      w =
+        Watchcat::Executor.new(
+          [File.dirname(path)],
+          recursive: false,
+          force_polling: force_polling,
+          poll_interval: poll_interval,
+          filters: {},
+          debounce: -1,
+          block: follower.method(:call),
+          tail: [path, offset]
         )
       w.start
       w
     end
+
+    # Feeds the events recorded with `record_to` through the same filters,
+    # conversion and debounce as `Watchcat.watch`, `speed` times as fast as
+    # they happened (`nil` for no pauses), and returns the number of events
+    # once the block has been called for all of them. The recorded options
+    # are used unless given, except `atomic_saves` and `normalize`, which
+    # can't be replayed.
+    def replay(file, speed: 1.0, **options, &block)
+      unreplayable = UNREPLAYABLE_OPTIONS.select { |name| options[name] }
+      raise ArgumentError, "#{unreplayable.join(" and ")} can't be used with replay" if unreplayable.any?
+
+      header = read_recording_header(file)
+      recorded = header.fetch("options", {}).transform_keys(&:to_sym)
+      defaults = recorded.slice(*REPLAYED_OPTIONS)
+      defaults[:filters] = recorded.slice(*REPLAYED_FILTERS).select { |_, v| v }
+
+      watcher = watch(header.fetch("paths"), **defaults.merge(options, backend: :fake), &block)
+      begin
+        watcher.replay(File.expand_path(file), speed&.to_f)
+      ensure
+        watcher.stop
+      end
+    end
+
+    # Blocks until an `event` on `path` arrives and returns it, or raises
+    # `Watchcat::Timeout` after `timeout` seconds (`nil` waits forever). With
+    # `pattern`, `path` is a directory, and the event is for a path below it
+    # that matches. Waiting for `:create` returns right away if the file
+    # already exists, and for `:remove` if it doesn't.
+    def wait_for(path, event: :create, timeout: 5, pattern: nil)
+      raise ArgumentError, "unknown event: #{event.inspect}" unless WAIT_FOR_EVENTS.include?(event)
+
+      path = File.expand_path(path)
+      args = Watchcat::Watcher.wait_for(path, event.to_s, timeout&.to_f, pattern)
+      raise Watchcat::Timeout, "no #{event} event for #{path} within #{timeout} seconds" if args.nil?
+
+      kind, paths, raw_kind, details = args
+      Watchcat::Event.new(kind, paths, raw_kind, **details)
+    end
+
+    # Whether `backend: :fanotify` can tell the process behind changes here:
+    # on Linux, with CAP_SYS_ADMIN. Otherwise it falls back to the native
+    # backend.
+    def fanotify_available?
+      __fanotify_available__
+    end
+
+    private
+
+    def read_recording_header(file)
+      header = JSON.parse(File.open(file, &:gets) || "null")
+      raise ArgumentError, "not a watchcat recording: #{file}" unless header.is_a?(Hash) && header.key?("watchcat")
+      raise ArgumentError, "unsupported recording format #{header["watchcat"]}: #{file}" unless header["watchcat"] == 1
+
+      header
+    rescue JSON::ParserError
+      raise ArgumentError, "not a watchcat recording: #{file}"
+    end
+  end
+end
+
+if (level = ENV["WATCHCAT_LOG"]) && !level.empty?
+  begin
+    Watchcat.logger = Logger.new($stderr, level: level)
+  rescue ArgumentError
+    warn "watchcat: ignoring WATCHCAT_LOG, not a log level: #{level}"
   end
 end
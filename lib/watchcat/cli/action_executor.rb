@@ -22,7 +22,9 @@ module Watchcat
         command = substitute_variables(action["command"])
         puts "Executing: #{command}"
 
-        success = system(command)
+        # The whole event, in the schema of `Watchcat::Event#to_json`, for
+        # commands that need more than the variables.
+        success = system({ "WATCHCAT_EVENT" => @event.to_json }, command)
         unless success
           puts "Command failed with exit code: #{$?.exitstatus}"
         end
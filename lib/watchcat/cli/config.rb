@@ -68,6 +68,9 @@ module Watchcat
             actions: watch_config["actions"] || [],
             debounce: watch_config.fetch("debounce", -1),
             filters: watch_config["filters"]&.transform_keys(&:to_sym) || {},
+            ignore_hidden: watch_config.fetch("ignore_hidden", false),
+            ignore_editor_temp: watch_config.fetch("ignore_editor_temp", false),
+            allow_missing: watch_config.fetch("allow_missing", false),
           }
         end
       end
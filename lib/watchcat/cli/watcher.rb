@@ -3,7 +3,9 @@ module Watchcat
     class Watcher
       def initialize(config)
         @config = config
-        @watchers = []
+        # One hub per combination of the options applied by the backend,
+        # which is usually just one for the whole config.
+        @hubs = {}
       end
 
       def start
@@ -25,8 +27,8 @@ module Watchcat
       end
 
       def stop
-        @watchers.each(&:stop)
-        @watchers.clear
+        @hubs.each_value(&:stop)
+        @hubs.clear
       end
 
       private
@@ -34,23 +36,32 @@ module Watchcat
       def start_watching_path(watch_config)
         path = watch_config[:path]
 
-        unless File.exist?(path)
+        if watch_config[:allow_missing]
+          puts "Waiting for: #{path}" unless File.exist?(path)
+        elsif !File.exist?(path)
           puts "Warning: Path does not exist: #{path}"
           return
         end
 
         puts "Watching: #{path} (recursive: #{watch_config[:recursive]}, debounce: #{watch_config[:debounce]}ms)"
 
-        watcher = Watchcat.watch(
+        hub_for(watch_config).subscribe(
           path,
           recursive: watch_config[:recursive],
-          filters: watch_config[:filters],
           debounce: watch_config[:debounce],
         ) do |event|
           handle_file_event(event, watch_config)
         end
+      end
 
-        @watchers << watcher
+      def hub_for(watch_config)
+        options = {
+          filters: watch_config[:filters],
+          ignore_hidden: watch_config[:ignore_hidden],
+          ignore_editor_temp: watch_config[:ignore_editor_temp],
+          allow_missing: watch_config[:allow_missing],
+        }
+        @hubs[options] ||= Watchcat::Hub.new(**options)
       end
 
       def handle_file_event(event, watch_config)
@@ -32,6 +32,13 @@ module Watchcat
       end
     end
 
+    # Waits for the pending timers to fire (or be replaced), and so for their
+    # blocks to be called.
+    def wait
+      timers = @mutex.synchronize { @timers.values }
+      timers.each(&:join)
+    end
+
     def pending_count
       @mutex.synchronize do
         @timers.size
@@ -0,0 +1,65 @@
+require_relative "debouncer"
+
+module Watchcat
+  # Applies the Ruby-side options (patterns, debounce, ...) to events and
+  # hands the remaining ones to a block and/or a Ractor port.
+  class Dispatcher
+    def initialize(block:, port: nil, debounce: -1, patterns: [], ignore_patterns: [], ignore_directories: false)
+      @block = block
+      @port = port
+      @debounce = debounce
+      @debouncer = Debouncer.new if @debounce > 0
+      @patterns = Array(patterns)
+      @ignore_patterns = Array(ignore_patterns)
+      @ignore_directories = ignore_directories
+    end
+
+    def call(event)
+      reason = dropped_by(event)
+      if reason
+        Watchcat::Logging.debug { "event dropped reason=#{reason} paths=#{event.paths.join(",")}" }
+        return
+      end
+
+      if @debounce > 0 && event.paths.size == 1
+        @debouncer.debounce(event.paths[0], @debounce) { deliver(event) }
+      else
+        deliver(event)
+      end
+    end
+
+    # Waits until the events held back by debounce have been delivered.
+    def flush
+      @debouncer&.wait
+    end
+
+    # The debouncer's timer threads don't survive a fork.
+    def after_fork
+      @debouncer = Debouncer.new if @debounce > 0
+    end
+
+    private
+
+    def deliver(event)
+      # Only shareable objects can be sent to another Ractor without being
+      # copied, so the event is deep-frozen first.
+      @port << Ractor.make_shareable(event) if @port
+      @block&.call(event)
+    end
+
+    # The option that rules out `event`, if any.
+    def dropped_by(event)
+      return :ignore_directories if @ignore_directories && event.directory?
+      return :patterns if @patterns.any? && !matches_any_pattern?(event.paths, @patterns)
+      return :ignore_patterns if @ignore_patterns.any? && matches_any_pattern?(event.paths, @ignore_patterns)
+
+      nil
+    end
+
+    def matches_any_pattern?(paths, patterns)
+      paths.any? do |path|
+        patterns.any? { |pattern| File.fnmatch?(pattern, File.basename(path)) || File.fnmatch?(pattern, path) }
+      end
+    end
+  end
+end
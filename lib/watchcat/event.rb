@@ -1,26 +1,125 @@
+require "json"
 require "watchcat/kind"
 
 module Watchcat
   class Event
-    attr_reader :kind, :paths, :raw_kind, :event
+    attr_reader :kind, :paths, :raw_kind, :event, :root, :relative_paths, :resolved_paths, :attributes, :normalized_kind, :process_name, :stats, :diff, :appended, :time
+
+    # Builds an event from what `#to_h` returns, or what `#to_json` returns
+    # once parsed (with string keys).
+    def self.from_h(hash)
+      hash = hash.transform_keys(&:to_sym)
+      new(
+        Array(hash[:kind]),
+        hash.fetch(:paths),
+        hash[:raw_kind],
+        root: hash[:root],
+        relative_paths: hash[:relative_paths],
+        resolved_paths: hash[:resolved_paths],
+        attributes: hash[:attributes]&.transform_keys(&:to_sym),
+        process_name: hash[:process_name],
+        stats: hash[:stats],
+        diff: hash[:diff],
+        normalized: hash[:normalized_kind],
+        directory: hash[:directory],
+        time: hash[:time]
+      )
+    end
 
-    def initialize(kinds, paths, raw_kind)
+    def initialize(kinds, paths, raw_kind, root: nil, relative_paths: nil, resolved_paths: nil, attributes: nil, process_name: nil, stats: nil, diff: nil, appended: nil, normalized: nil, directory: nil, time: nil)
       @paths = paths
       @raw_kind = raw_kind
+      @root = root
+      @relative_paths = relative_paths || Array.new(paths.size)
+      @resolved_paths = resolved_paths || paths
+      @attributes = Watchcat::EventAttributes.new(**(attributes || {}))
+      # The name of the process behind the event, if it was known and still
+      # running when the event was reported (`backend: :fanotify`).
+      @process_name = process_name
+      # With `stat: true`, a `Watchcat::FileStat` for each path, `nil` for
+      # the ones that were gone.
+      @stats = stats&.map { |stat| stat && build_stat(stat) }
+      # With `track_content`, a `Watchcat::ContentDiff` when the contents of
+      # a matching file changed.
+      @diff = diff && build_diff(diff)
+      # For `Watchcat.tail`, the bytes appended to the file: one String for
+      # each file read, so two when it was rotated.
+      @appended = appended
+      # Only set with `normalize: true`: one of :created, :modified, :removed,
+      # :moved or :metadata_changed, and whether the path is a directory.
+      @normalized_kind = normalized&.to_sym
+      @directory = directory
+      # When the extension reported the event.
+      @time = time && Time.at(time)
       build_kind(kinds)
     end
 
+    # The event as a Hash of plain values, in a schema kept stable across
+    # versions (see "Serializing Events" in the README). `Event.from_h` turns
+    # it back into an event.
+    def to_h
+      {
+        kind: @kinds,
+        event: @event,
+        paths: @paths,
+        src_path: src_path,
+        dest_path: dest_path,
+        raw_kind: @raw_kind,
+        root: @root,
+        relative_paths: @relative_paths,
+        resolved_paths: @resolved_paths,
+        attributes: @attributes.to_h,
+        pid: pid,
+        process_name: @process_name,
+        stats: @stats&.map { |stat| stat && stat.to_h.merge(mtime: stat.mtime&.to_f) },
+        diff: @diff && { unified: @diff.unified, hunks: @diff.hunks.map { |hunk| hunk.map { |lines| [lines.begin, lines.end] } } },
+        normalized_kind: @normalized_kind&.to_s,
+        directory: @directory,
+        time: @time&.to_f
+      }
+    end
+
+    def to_json(*args)
+      to_h.to_json(*args)
+    end
+
     def deconstruct_keys(_keys)
-      { paths: @paths, event: @event }
+      { paths: @paths, event: @event, root: @root, relative_paths: @relative_paths, normalized_kind: @normalized_kind }
+    end
+
+    # The process behind the event, where the backend reports it
+    # (`backend: :fanotify`).
+    def pid
+      @attributes.process_id
+    end
+
+    # The `Watchcat::FileStat` of the first path, with `stat: true`.
+    def stat
+      @stats&.first
+    end
+
+    # Whether a watched path (one given to `Watchcat.watch` or `#watch`) was
+    # removed or moved away. It's watched again once it's back, which is
+    # reported by `root_restored?`.
+    def root_removed?
+      kind.other? && @attributes.info == "root_removed"
+    end
+
+    def root_restored?
+      kind.other? && @attributes.info == "root_restored"
     end
 
     def directory?
+      return @directory unless @directory.nil?
+      return stat.directory? if stat
+
       if kind.create?
         kind.create.folder?
       elsif kind.remove?
         kind.remove.folder?
-      elsif kind.any?
-        kind.any.folder?
+      elsif @stats
+        # Gone by the time the event was received.
+        false
       else
         File.directory?(@paths.first)
       end
@@ -53,22 +152,31 @@ module Watchcat
       kind.modify? && kind.modify.rename?
     end
 
+    def build_stat(stat)
+      stat = stat.transform_keys(&:to_sym)
+      Watchcat::FileStat.new(**stat.merge(mtime: stat[:mtime] && Time.at(stat[:mtime])))
+    end
+
+    def build_diff(diff)
+      diff = diff.transform_keys(&:to_sym)
+      hunks = diff[:hunks].map { |hunk| hunk.map { |first, last| first...last } }
+      Watchcat::ContentDiff.new(unified: diff[:unified], hunks: hunks)
+    end
+
     def build_kind(kinds)
+      kinds = kinds.dup
       @kind = Watchcat::EventKind.new
-      @event = kinds.shift
-      if event
-        @kind.public_send("#{event}=", Object.const_get("Watchcat::#{event.capitalize}Kind").new)
-        send("build_#{event}_kind", kinds)
-      else
-        @kind.any = Watchcat::AnyKind.new
-        if File.directory?(@paths.first)
-          @kind.any.kind = "folder"
-        else
-          @kind.any.kind = "file"
-        end
-      end
+      @event = kinds.shift || "any"
+      # The whole hierarchy, for `#to_h`.
+      @kinds = [@event, *kinds]
+      @kind.public_send("#{event}=", Object.const_get("Watchcat::#{event.capitalize}Kind").new)
+      send("build_#{event}_kind", kinds)
     end
 
+    def build_any_kind(_kinds); end
+
+    def build_other_kind(_kinds); end
+
     def build_access_kind(kinds)
       @kind.access.kind = kinds.shift
 
@@ -1,38 +1,118 @@
 require_relative "event"
+require_relative "dispatcher"
 
 module Watchcat
   class Executor
-    def initialize(paths, recursive:, force_polling:, poll_interval:, filters:, debounce:, block:, patterns: [], ignore_patterns: [], ignore_directories: false)
-      @paths = paths
+    # Files larger than this aren't tracked by `track_content` unless given
+    # `max_bytes`.
+    TRACK_CONTENT_MAX_BYTES = 1024 * 1024
+    # How much `track_content` keeps over all files unless given
+    # `max_total_bytes`.
+    TRACK_CONTENT_MAX_TOTAL_BYTES = 64 * 1024 * 1024
+
+    # Watchers started in the main Ractor and not stopped yet, for re-arming
+    # in a forked child.
+    @running = []
+    @running_lock = Mutex.new
+
+    class << self
+      def register(executor)
+        @running_lock.synchronize { @running << executor }
+      end
+
+      def unregister(executor)
+        @running_lock.synchronize { @running.delete(executor) }
+      end
+
+      # Called in a forked child by `Watchcat::ForkHook`.
+      def after_fork
+        # Another thread of the parent may have held the lock when forking,
+        # and that thread doesn't exist in the child.
+        @running_lock = Mutex.new
+        @running.select(&:rearm_on_fork?).each(&:after_fork)
+      end
+    end
+
+    def initialize(paths, recursive:, force_polling:, poll_interval:, filters:, debounce:, block:, patterns: [], ignore_patterns: [], ignore_directories: false, follow_symlinks: nil, depth: nil, atomic_saves: false, normalize: false, stat: false, track_content: nil, tail: nil, allow_missing: false, ignore_hidden: false, ignore_editor_temp: false, port: nil, fiber: false, backend: nil, record_to: nil)
+      @allow_missing = allow_missing
+      # A path that doesn't exist has no current directory to be relative
+      # to once it appears.
+      @paths = allow_missing ? paths.map { |p| File.expand_path(p) } : paths
       @recursive = recursive
       @force_polling = force_polling
       @poll_interval = poll_interval
+      @follow_symlinks = follow_symlinks
+      @depth = depth
+      @atomic_saves = atomic_saves
+      @normalize = normalize
+      @stat = stat
+      @track_content = build_track_content(track_content)
+      @tail = tail
       @filters = filters || {}
-      @debounce = debounce
-      @debouncer = Debouncer.new if @debounce > 0
-      @patterns = Array(patterns)
-      @ignore_patterns = Array(ignore_patterns)
-      @ignore_directories = ignore_directories
-      @block = block
+      @ignore_hidden = ignore_hidden
+      @ignore_editor_temp = ignore_editor_temp
+      @dispatcher = Dispatcher.new(
+        block: block,
+        port: port,
+        debounce: debounce,
+        patterns: patterns,
+        ignore_patterns: ignore_patterns,
+        ignore_directories: ignore_directories
+      )
+      @fiber = fiber
+      @backend = backend&.to_sym
+      # Also rejected by the extension, but that would only fail the watcher
+      # thread.
+      raise ArgumentError, "backend: :fanotify can't be used with force_polling: true" if @backend == :fanotify && force_polling
+      @record_to = record_to
       @watcher = Watchcat::Watcher.new
       @watch_thread = nil
+      @watch_fiber = nil
       @stop_requested = false
       @owner_pid = nil
       @stopped = false
+      @inherited_watchers = []
     end
 
     def start
-      @owner_pid = Process.pid
+      run
 
-      # Always start watching in a background thread to avoid blocking
-      @watch_thread = Thread.new do
-        Thread.current.name = "watchcat-watcher"
-        start_watching
+      # Exit hooks can only be registered from the main Ractor. Watchers
+      # started elsewhere have to be stopped explicitly.
+      if main_ractor?
+        at_exit do
+          stop
+        end
+        self.class.register(self)
       end
+    end
 
-      at_exit do
-        stop
-      end
+    # Watches again in a forked child, which inherits this object but not the
+    # native threads behind it, with the watcher `Watcher#after_fork` rebuilds
+    # on the paths watched at the time of the fork. Called automatically on fork (see `rearm_on_fork?`). A no-op in
+    # the process that started watching, or once stopped.
+    def after_fork
+      return self if @owner_pid.nil? || @owner_pid == Process.pid || @stopped
+
+      raise ArgumentError, "fiber: true requires a Fiber scheduler" if @fiber && !Fiber.scheduler
+
+      @owner_pid = Process.pid
+      # Kept rather than closed or left to the GC: the parent's watcher thread
+      # may have held locks the native watcher needs when it forked.
+      @inherited_watchers << @watcher
+      @watcher = @watcher.after_fork
+      @dispatcher.after_fork
+      @watch_thread = nil
+      @watch_fiber = nil
+      in_background { drain }
+      self
+    end
+
+    # Whether a forked child re-arms this watcher on its own. Not with
+    # `fiber: true`, which needs the child to set up a Fiber scheduler first,
+    # nor for FSEvents, which can't be used in a forked child.
+    def rearm_on_fork?
+      !@fiber && !(RUBY_PLATFORM.match?("darwin") && !@force_polling)
     end
 
     def stop
@@ -44,6 +124,7 @@ module Watchcat
 
       return if @stopped
       @stopped = true
+      self.class.unregister(self) if main_ractor?
 
       @stop_requested = true
       @watcher.close
@@ -52,16 +133,22 @@ module Watchcat
       end
     end
 
-    def watch(paths, recursive: @recursive)
+    def watch(paths, recursive: @recursive, depth: @depth)
       paths = Array(paths)
-      paths.each { |p| raise ArgumentError, "path does not exist: #{p}" unless File.exist?(p) }
-      @watcher.add(paths, recursive: recursive)
+      if @allow_missing
+        paths = paths.map { |p| File.expand_path(p) }
+      elsif !fake?
+        # Nothing is watched for real with the fake backend.
+        paths.each { |p| raise ArgumentError, "path does not exist: #{p}" unless File.exist?(p) }
+      end
+      @watcher.add(paths, recursive: recursive, depth: depth)
       @paths |= paths
       self
     end
 
     def unwatch(paths)
       paths = Array(paths)
+      paths = paths.map { |p| File.expand_path(p) } if @allow_missing
       @watcher.unwatch(paths)
       @paths -= paths
       self
@@ -71,50 +158,134 @@ module Watchcat
       @paths.dup
     end
 
-    # Whether the background watcher thread is still running. `false` before
-    # `#start` is called, after `#stop`, or if the thread died unexpectedly.
+    # Whether the background watcher thread (or fiber, with `fiber: true`) is
+    # still running. `false` before `#start` is called, after `#stop`, or if
+    # it died unexpectedly.
     def alive?
+      return !@watch_fiber.nil? && @watch_fiber.alive? if @fiber
+
       !@watch_thread.nil? && @watch_thread.alive?
     end
 
+    # Hands a made-up event to the watcher, as if the backend had reported
+    # it. Only with `backend: :fake`. See `Watchcat::Testing.inject`.
+    def inject(kind, paths)
+      raise ArgumentError, "events can only be injected with backend: :fake" unless fake?
+
+      # The watch loop waits for the block to return, so an event injected
+      # from the block can't be waited for.
+      @watcher.inject(kind.to_s, Array(paths), Thread.current != @watch_thread)
+    end
+
+    # Feeds a recording to the watcher. Only with `backend: :fake`. See
+    # `Watchcat.replay`.
+    def replay(file, speed)
+      raise ArgumentError, "recordings can only be replayed with backend: :fake" unless fake?
+
+      replayed = @watcher.replay(file, speed)
+      @dispatcher.flush
+      replayed
+    end
+
     private
 
+    def fake?
+      @backend == :fake
+    end
+
+    # `track_content` as the extension takes it:
+    # `[patterns, max_bytes, max_total_bytes]`. `true` tracks every file.
+    def build_track_content(track_content)
+      return nil unless track_content
+      return [["*"], TRACK_CONTENT_MAX_BYTES, TRACK_CONTENT_MAX_TOTAL_BYTES] if track_content == true
+
+      patterns = Array(track_content.fetch(:patterns) { raise ArgumentError, "track_content needs patterns:" })
+      [
+        patterns.map(&:to_s),
+        Integer(track_content.fetch(:max_bytes, TRACK_CONTENT_MAX_BYTES)),
+        Integer(track_content.fetch(:max_total_bytes, TRACK_CONTENT_MAX_TOTAL_BYTES))
+      ]
+    end
+
+    def run
+      raise ArgumentError, "fiber: true requires a Fiber scheduler" if @fiber && !Fiber.scheduler
+
+      @owner_pid = Process.pid
+
+      if @fiber
+        @watcher.start(@paths, **watch_options)
+        in_background { drain }
+      else
+        in_background { start_watching }
+      end
+    end
+
+    # Always watches in a background thread (or fiber) to avoid blocking.
+    def in_background(&block)
+      if @fiber
+        @watch_fiber = Fiber.schedule(&block)
+      else
+        @watch_thread = Thread.new do
+          Thread.current.name = "watchcat-watcher"
+          block.call
+        end
+      end
+    end
+
     def start_watching
-      @watcher.watch(
-        @paths,
+      @watcher.watch(@paths, **watch_options) do |kind, paths, raw_kind, details|
+        next if @stop_requested
+
+        handle(Watchcat::Event.new(kind, paths, raw_kind, **details))
+      end
+    end
+
+    # Handles the events of a watcher started with `#start` until it's
+    # closed. Events are queued by the extension, which makes `fd` readable
+    # whenever there are some, so this only runs while there is work to do.
+    def drain
+      io = @watcher.to_io
+      loop do
+        io.wait_readable
+        events = @watcher.drain_events
+        break if events.nil?
+
+        events.each { |event| handle(event) }
+      end
+    end
+
+    def watch_options
+      {
         recursive: @recursive,
         force_polling: @force_polling,
         poll_interval: @poll_interval,
+        follow_symlinks: @follow_symlinks,
+        depth: @depth,
+        atomic_saves: @atomic_saves,
+        normalize: @normalize,
+        stat: @stat,
+        track_content: @track_content,
+        tail: @tail,
+        allow_missing: @allow_missing,
         ignore_remove: @filters[:ignore_remove],
         ignore_access: @filters[:ignore_access],
         ignore_create: @filters[:ignore_create],
-        ignore_modify: @filters[:ignore_modify]
-      ) do |kind, paths, raw_kind|
-        next if @stop_requested
-
-        event = Watchcat::Event.new(kind, paths, raw_kind)
-        next unless dispatch?(event)
-
-        if @debounce > 0 && paths.size == 1
-          @debouncer.debounce(paths[0], @debounce) { @block.call(event) }
-        else
-          @block.call(event)
-        end
-      end
+        ignore_modify: @filters[:ignore_modify],
+        ignore_hidden: @ignore_hidden,
+        ignore_editor_temp: @ignore_editor_temp,
+        backend: @backend&.to_s,
+        record_to: @record_to
+      }
     end
 
-    def dispatch?(event)
-      return false if @ignore_directories && event.directory?
-      return false if @patterns.any? && !matches_any_pattern?(event.paths, @patterns)
-      return false if @ignore_patterns.any? && matches_any_pattern?(event.paths, @ignore_patterns)
+    def handle(event)
+      return if @stop_requested
 
-      true
+      @dispatcher.call(event)
     end
 
-    def matches_any_pattern?(paths, patterns)
-      paths.any? do |path|
-        patterns.any? { |pattern| File.fnmatch?(pattern, File.basename(path)) || File.fnmatch?(pattern, path) }
-      end
+    def main_ractor?
+      !Ractor.respond_to?(:main) || Ractor.current == Ractor.main
     end
   end
 end
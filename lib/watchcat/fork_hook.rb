@@ -0,0 +1,19 @@
+require_relative "executor"
+
+module Watchcat
+  # Re-arms running watchers in a child created by `Kernel#fork`,
+  # `Process.fork` or `IO.popen("-")`, which Ruby routes through
+  # `Process._fork`.
+  module ForkHook
+    def _fork
+      pid = super
+      if pid.zero?
+        Watchcat::Logging.after_fork
+        Watchcat::Executor.after_fork
+      end
+      pid
+    end
+  end
+end
+
+Process.singleton_class.prepend(Watchcat::ForkHook)
@@ -0,0 +1,207 @@
+require_relative "executor"
+require_relative "dispatcher"
+
+module Watchcat
+  # Runs many subscriptions on one native watcher (one inotify instance, for
+  # example) and one thread, routing each event to the subscriptions whose
+  # paths it is under.
+  #
+  # Options that configure the backend are given to the hub and shared by
+  # every subscription; the rest are given per subscription. `filters` can be
+  # given to either: the hub's are applied by the backend.
+  class Hub
+    class Subscription
+      attr_reader :paths, :recursive, :depth
+
+      def initialize(hub, paths, recursive:, depth:, filters:, dispatcher:)
+        @hub = hub
+        @paths = paths
+        @recursive = recursive
+        @depth = depth
+        @filters = filters || {}
+        @dispatcher = dispatcher
+      end
+
+      def cancel
+        @hub.unsubscribe(self)
+      end
+
+      def covers?(event)
+        event_paths(event).any? { |path| @paths.any? { |root| within?(root, path) } }
+      end
+
+      def call(event)
+        return if ignored_kind?(event.kind)
+
+        @dispatcher.call(event)
+      end
+
+      private
+
+      # Backends that report canonical paths (FSEvents) don't match the
+      # subscribed paths, but the paths relative to the watch root do.
+      def event_paths(event)
+        paths = event.paths.dup
+        if event.root
+          event.relative_paths.compact.each do |relative|
+            paths << (relative == "." ? event.root : File.join(event.root, relative))
+          end
+        end
+        File::ALT_SEPARATOR ? paths.map { |path| path.tr(File::ALT_SEPARATOR, File::SEPARATOR) } : paths
+      end
+
+      def within?(root, path)
+        return true if path == root
+
+        prefix = root.end_with?(File::SEPARATOR) ? root : "#{root}#{File::SEPARATOR}"
+        return false unless path.start_with?(prefix)
+
+        levels = path.delete_prefix(prefix).count(File::SEPARATOR) + 1
+        return levels == 1 unless @recursive
+
+        @depth.nil? || levels <= @depth
+      end
+
+      def ignored_kind?(kind)
+        (@filters[:ignore_remove] && kind.remove?) ||
+          (@filters[:ignore_access] && kind.access?) ||
+          (@filters[:ignore_create] && kind.create?) ||
+          (@filters[:ignore_modify] && kind.modify?)
+      end
+    end
+
+    def initialize(force_polling: false, poll_interval: nil, follow_symlinks: nil, atomic_saves: false, normalize: false, filters: {}, ignore_hidden: false, ignore_editor_temp: false, allow_missing: false)
+      @subscriptions = []
+      @allow_missing = allow_missing
+      # How each path is watched natively: `[recursive, depth]`.
+      @watched = {}
+      @lock = Mutex.new
+      @executor =
+        Watchcat::Executor.new(
+          [],
+          recursive: true,
+          force_polling: force_polling,
+          poll_interval: poll_interval,
+          follow_symlinks: follow_symlinks,
+          atomic_saves: atomic_saves,
+          normalize: normalize,
+          allow_missing: allow_missing,
+          filters: filters,
+          debounce: -1,
+          ignore_hidden: ignore_hidden,
+          ignore_editor_temp: ignore_editor_temp,
+          block: method(:route)
+        )
+      @executor.start
+    end
+
+    def subscribe(
+      paths,
+      recursive: true,
+      depth: nil,
+      filters: {},
+      debounce: -1,
+      patterns: [],
+      ignore_patterns: [],
+      ignore_directories: false,
+      handler: nil,
+      port: nil,
+      &block
+    )
+      callback = block || (handler && handler.method(:dispatch))
+      raise ArgumentError, "must provide a block, a handler: or a port:" unless callback || port
+
+      paths = Array(paths).map { |p| File.expand_path(p) }
+      unless @allow_missing
+        paths.each { |p| raise ArgumentError, "path does not exist: #{p}" unless File.exist?(p) }
+      end
+
+      dispatcher = Dispatcher.new(
+        block: callback,
+        port: port,
+        debounce: debounce,
+        patterns: patterns,
+        ignore_patterns: ignore_patterns,
+        ignore_directories: ignore_directories
+      )
+      subscription = Subscription.new(self, paths, recursive: recursive, depth: depth, filters: filters, dispatcher: dispatcher)
+
+      @lock.synchronize do
+        @subscriptions << subscription
+        rewatch
+      end
+      subscription
+    end
+
+    def unsubscribe(subscription)
+      @lock.synchronize do
+        rewatch if @subscriptions.delete(subscription)
+      end
+      self
+    end
+
+    def subscriptions
+      @lock.synchronize { @subscriptions.dup }
+    end
+
+    def stop
+      @executor.stop
+    end
+
+    def alive?
+      @executor.alive?
+    end
+
+    private
+
+    def route(event)
+      subscriptions.each do |subscription|
+        subscription.call(event) if subscription.covers?(event)
+      end
+    end
+
+    # Watches each subscribed path natively as deep as its deepest
+    # subscription needs, except for the ones within a path watched
+    # recursively without a depth limit, and unwatches the rest.
+    def rewatch
+      wanted = {}
+      @subscriptions.flat_map(&:paths).uniq.each do |path|
+        wanted[path] = watch_settings(@subscriptions.select { |s| s.paths.include?(path) })
+      end
+      wanted.reject! { |path, _| wanted.any? { |other, settings| settings == [true, nil] && within?(other, path) } }
+
+      stale = @watched.keys.reject { |path| @watched[path] == wanted[path] }
+      stale.each do |path|
+        @executor.unwatch(path)
+        @watched.delete(path)
+      end
+      # inotify shares watches between overlapping paths, and unwatching one
+      # drops them for everything below it, so the paths overlapping an
+      # unwatched one are watched again.
+      overlapping = @watched.keys.select { |path| stale.any? { |gone| within?(path, gone) || within?(gone, path) } }
+      overlapping.each { |path| @watched.delete(path) }
+
+      wanted.each do |path, settings|
+        next if @watched[path] == settings
+
+        recursive, depth = settings
+        @executor.watch(path, recursive: recursive, depth: depth)
+        @watched[path] = settings
+      end
+    end
+
+    # Whether `path` is strictly below `root`.
+    def within?(root, path)
+      prefix = root.end_with?(File::SEPARATOR) ? root : "#{root}#{File::SEPARATOR}"
+      path.start_with?(prefix)
+    end
+
+    def watch_settings(subscriptions)
+      recursive = subscriptions.select(&:recursive)
+      return [false, nil] if recursive.empty?
+
+      depths = recursive.map(&:depth)
+      [true, depths.include?(nil) ? nil : depths.max]
+    end
+  end
+end
@@ -2,10 +2,10 @@ require "forwardable"
 
 module Watchcat
   class EventKind
-    attr_accessor :access, :create, :modify, :remove, :any
+    attr_accessor :access, :create, :modify, :remove, :any, :other
 
     def initialize
-      @access, @create, @modify, @remove, @any = nil, nil, nil, nil,nil
+      @access, @create, @modify, @remove, @any, @other = nil, nil, nil, nil, nil, nil
     end
 
     def access?
@@ -28,11 +28,17 @@ module Watchcat
       !@any.nil?
     end
 
+    def other?
+      !@other.nil?
+    end
+
     def event_type
       return "create" if create?
       return "modify" if modify?
       return "remove" if remove?
       return "access" if access?
+      return "any" if any?
+      return "other" if other?
       "unknown"
     end
   end
@@ -109,18 +115,59 @@ module Watchcat
     end
   end
 
+  # An event the backend couldn't classify at all.
   class AnyKind
     attr_accessor :kind
 
+    # Deprecated: whether the path was a file or a folder used to be guessed
+    # when the event was received, and nothing is known about it now.
     def file?
-      @kind == "file"
+      warn "Watchcat::AnyKind#file? is deprecated and always false", uplevel: 1, category: :deprecated
+      false
     end
 
+    # Deprecated, like `#file?`.
     def folder?
-      @kind == "folder"
+      warn "Watchcat::AnyKind#folder? is deprecated and always false", uplevel: 1, category: :deprecated
+      false
+    end
+  end
+
+  # A backend-specific event that doesn't fit any of the other kinds, like
+  # the meta events of some backends. Look at `Event#attributes` to tell them
+  # apart.
+  class OtherKind
+  end
+
+  # notify's event attributes: `tracker` ties together events that belong to
+  # the same operation (like the two halves of a rename), `flag` is
+  # `"rescan"` when events may have been missed, `info` and `source` are
+  # backend-specific details, and `process_id` is the process behind the
+  # event where the backend reports it.
+  EventAttributes = Struct.new(:tracker, :flag, :info, :source, :process_id, keyword_init: true)
+
+  # What `lstat` said about a path when the event was received, with
+  # `stat: true`. `type` is "file", "directory", "link" or "other", and
+  # `mode` and `ino` are `nil` where the platform has no such thing.
+  FileStat = Struct.new(:size, :mtime, :mode, :ino, :type, keyword_init: true) do
+    def file?
+      type == "file"
+    end
+
+    def directory?
+      type == "directory"
+    end
+
+    def symlink?
+      type == "link"
     end
   end
 
+  # What changed in a file, with `track_content`: `unified` is a unified diff
+  # against the contents at the previous event, and `hunks` holds the changed
+  # lines of each hunk as `[before, after]` ranges of line numbers (1-based,
+  # empty for lines that were only added or only removed).
+  ContentDiff = Struct.new(:unified, :hunks, keyword_init: true)
 
   class AccessMode
     attr_accessor :mode
@@ -0,0 +1,83 @@
+require "logger"
+
+module Watchcat
+  # Hands what the extension logs to `Watchcat.logger`. The extension only
+  # buffers its records, as it mostly runs without the GVL or on threads of
+  # its own, and a thread of ours takes them from there.
+  module Logging
+    PROGNAME = "watchcat".freeze
+
+    @logger = nil
+    @thread = nil
+    @lock = Mutex.new
+
+    class << self
+      attr_reader :logger
+
+      def logger=(logger)
+        @lock.synchronize do
+          @logger = logger
+          # Records are dropped on the Rust side unless at the logger's level.
+          Watchcat.__set_log_level__(logger&.level)
+          start_thread if logger && !@thread&.alive?
+        end
+      end
+
+      # Logs from Ruby alongside the extension. Only from the main Ractor,
+      # which the logger belongs to.
+      def debug(&block)
+        return unless main_ractor?
+
+        @logger&.debug(PROGNAME, &block)
+      end
+
+      # Writes out what is still buffered, e.g. at exit.
+      def flush
+        write(Watchcat.__take_log_records__(false))
+      end
+
+      # The thread doesn't survive a fork.
+      def after_fork
+        @lock = Mutex.new
+        @thread = nil
+        start_thread if @logger
+      end
+
+      private
+
+      def start_thread
+        @thread = Thread.new do
+          Thread.current.name = "watchcat-logger"
+          loop { write(Watchcat.__take_log_records__(true)) }
+        end
+      end
+
+      def main_ractor?
+        !Ractor.respond_to?(:main) || Ractor.current == Ractor.main
+      end
+
+      def write(records)
+        logger = @logger
+        return unless logger
+
+        records.each { |severity, message| logger.add(severity, message, PROGNAME) }
+      end
+    end
+  end
+
+  at_exit { Logging.flush }
+
+  class << self
+    def logger
+      Logging.logger
+    end
+
+    # Sets a `Logger` (or anything that responds to `#add` and `#level`) for
+    # what happens inside watchcat: what is watched, each event the backend
+    # reports, the events dropped by the filters and why, and failures. Most
+    # is logged at the debug level. `nil` turns logging off.
+    def logger=(logger)
+      Logging.logger = logger
+    end
+  end
+end
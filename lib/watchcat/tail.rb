@@ -0,0 +1,50 @@
+module Watchcat
+  # Hands what the extension reads from the file `Watchcat.tail` follows to
+  # the block, a line (or chunk) at a time.
+  class Tail
+    def initialize(lines:, &block)
+      @lines = lines
+      @block = block
+      @buffer = +""
+    end
+
+    # Yields what is already in the file, for `from: :start`, and returns the
+    # offset to follow it from.
+    def read_existing(path)
+      data = File.binread(path)
+      push(data, nil)
+      data.bytesize
+    rescue Errno::ENOENT
+      0
+    end
+
+    def call(event)
+      event.appended&.each_with_index do |chunk, i|
+        # A chunk after the first is from a new file at the path (rotated),
+        # so the last line of the old one is complete even without a newline.
+        flush(event) if i > 0
+        push(chunk, event)
+      end
+    end
+
+    private
+
+    def push(chunk, event)
+      chunk = chunk.dup.force_encoding(Encoding.default_external)
+      return @block.call(chunk, event) unless @lines
+
+      @buffer << chunk
+      while (newline = @buffer.index("\n"))
+        @block.call(@buffer.slice!(0..newline).chomp, event)
+      end
+    end
+
+    def flush(event)
+      return if @buffer.empty?
+
+      line = @buffer
+      @buffer = +""
+      @block.call(line, event)
+    end
+  end
+end
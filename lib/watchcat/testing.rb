@@ -0,0 +1,27 @@
+require "watchcat"
+
+module Watchcat
+  # Helpers for testing code that uses `Watchcat.watch` without touching the
+  # filesystem. Watch with `backend: :fake`, and inject the events the code
+  # under test should see:
+  #
+  #   watcher = Watchcat.watch("/app/config", backend: :fake) { |e| reload(e.paths) }
+  #   Watchcat::Testing.inject(watcher, kind: :modify, paths: "/app/config/app.yml")
+  module Testing
+    module_function
+
+    # Sends an event through the same pipeline as a real one (the filters,
+    # `normalize`, the patterns and debounce) and returns `true` once it has
+    # been handled, so the block has been called for it unless it was
+    # filtered out or held back by `debounce`. Returns `false` if the watcher
+    # was stopped. With `fiber: true`, it returns once the event is queued for
+    # the fiber.
+    #
+    # `kind` is one of `:create`, `:create_folder`, `:modify`, `:metadata`,
+    # `:rename` (with the old and the new path), `:rename_from`, `:rename_to`,
+    # `:remove`, `:remove_folder`, `:access`, `:any` or `:other`.
+    def inject(watcher, kind:, paths:)
+      watcher.inject(kind, paths)
+    end
+  end
+end
@@ -0,0 +1,77 @@
+require_relative "event"
+
+module Watchcat
+  # Methods on top of the native watcher for using it from an event loop:
+  # after `#start`, `#to_io` becomes readable whenever `#drain_events` has
+  # something to return.
+  class Watcher
+    # Keeps what the watcher was started with, and the paths added and
+    # removed since, for `#after_fork`.
+    module Arguments
+      # Before the native `#watch`, which only returns once closed.
+      def watch(paths, **options, &block)
+        remember(paths, options)
+        super
+      end
+
+      def start(paths, **options)
+        fd = super
+        remember(paths, options)
+        fd
+      end
+
+      def add(paths, **options)
+        Array(paths).each { |path| @added[path] = options } if !frozen? && @added
+        super
+      end
+
+      def unwatch(paths)
+        if !frozen? && @added
+          Array(paths).each { |path| @added.delete(path) }
+          @paths -= Array(paths)
+        end
+        super
+      end
+
+      private
+
+      def remember(paths, options)
+        return if frozen?
+
+        @paths = Array(paths)
+        @options = options
+        @added = {}
+      end
+    end
+    prepend Arguments
+
+    def to_io
+      # A frozen (e.g. Ractor-shared) watcher can't memoize.
+      return IO.for_fd(fileno, autoclose: false) if frozen?
+
+      @io ||= IO.for_fd(fileno, autoclose: false)
+    end
+
+    # Returns the pending events without blocking, or `nil` once the watcher
+    # has been closed and every event has been returned.
+    def drain_events
+      drain_raw_events&.map do |kind, paths, raw_kind, details|
+        Watchcat::Event.new(kind, paths, raw_kind, **details)
+      end
+    end
+
+    # For a forked child, which inherits this watcher but not the native
+    # thread behind it: returns a new watcher, started with `#start` on the
+    # paths this one watches (added and removed ones included) with the same
+    # options. This one is left as it is, as the parent's thread may have
+    # held locks its native state needs when it forked.
+    def after_fork
+      raise "watcher not started" unless @options
+
+      watcher = self.class.new
+      watcher.start(@paths, **@options)
+      @added.each { |path, options| watcher.add([path], **options) }
+      watcher
+    end
+  end
+end
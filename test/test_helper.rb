@@ -16,6 +16,11 @@ def inspect_events(events)
   events.map {|e| [e.paths, e.raw_kind] }
 end
 
+# Takes what has been pushed to `queue` so far, without waiting for more.
+def received(queue = @events)
+  Array.new(queue.size) { queue.pop }
+end
+
 def mac_os?
   RUBY_PLATFORM.match?("darwin")
 end
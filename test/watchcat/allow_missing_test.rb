@@ -0,0 +1,102 @@
+# frozen_string_literal: true
+
+require "test_helper"
+require "tmpdir"
+require "fileutils"
+
+class Watchcat::AllowMissingTest < Minitest::Test
+  def setup
+    @tmpdir = Dir.mktmpdir("watchcat")
+    @dist = File.join(@tmpdir, "build", "dist")
+    @events = Queue.new
+  end
+
+  def teardown
+    @watchcat&.stop
+    FileUtils.remove_entry_secure(@tmpdir)
+  end
+
+  def test_missing_path_raises_without_allow_missing
+    assert_raises(ArgumentError) do
+      @watchcat = Watchcat.watch(@tmpdir) { |e| @events << e }
+      @watchcat.watch(@dist)
+    end
+  end
+
+  def test_reports_the_path_being_created
+    watch
+    FileUtils.mkdir_p(File.join(@dist, "js"))
+    File.write(File.join(@dist, "js", "app.js"), "x")
+    sleep 0.5
+
+    events = received
+    assert events.any? { |e| e.kind.create? && e.paths == [@dist] }
+    assert events.any? { |e| e.kind.create? && e.paths == [File.join(@dist, "js", "app.js")] }
+  end
+
+  def test_watches_the_path_once_created
+    watch
+    FileUtils.mkdir_p(@dist)
+    sleep 0.3
+    received
+    File.write(File.join(@dist, "app.js"), "x")
+    sleep 0.5
+
+    assert received.any? { |e| e.paths == [File.join(@dist, "app.js")] }
+  end
+
+  def test_ignores_the_rest_of_the_ancestor
+    watch
+    FileUtils.mkdir_p(File.join(@tmpdir, "build"))
+    File.write(File.join(@tmpdir, "build", "other.txt"), "x")
+    File.write(File.join(@tmpdir, "noise.txt"), "x")
+    sleep 0.5
+
+    assert_empty received
+  end
+
+  def test_survives_the_path_being_removed_and_recreated
+    FileUtils.mkdir_p(@dist)
+    watch
+    FileUtils.rm_rf(@dist)
+    sleep 0.3
+    FileUtils.mkdir_p(@dist)
+    sleep 0.3
+    File.write(File.join(@dist, "app.js"), "x")
+    sleep 0.5
+
+    events = received
+    assert events.any? { |e| e.kind.remove? && e.paths == [@dist] }
+    assert events.any? { |e| e.kind.create? && e.paths == [@dist] }
+    assert events.any? { |e| e.paths == [File.join(@dist, "app.js")] }
+  end
+
+  def test_watch_and_unwatch_a_missing_path
+    @watchcat = Watchcat.watch([], allow_missing: true, filters: { ignore_access: true }) { |e| @events << e }
+    @watchcat.watch(@dist)
+    sleep 0.2
+    assert_equal [@dist], @watchcat.watched
+
+    @watchcat.unwatch(@dist)
+    sleep 0.2
+    FileUtils.mkdir_p(@dist)
+    sleep 0.5
+
+    assert_empty received
+  end
+
+  def test_relative_paths_are_expanded
+    Dir.chdir(@tmpdir) do
+      @watchcat = Watchcat.watch("build/dist", allow_missing: true) { |e| @events << e }
+    end
+
+    assert_equal [@dist], @watchcat.watched
+  end
+
+  private
+
+  def watch
+    @watchcat = Watchcat.watch(@dist, allow_missing: true, filters: { ignore_access: true }) { |e| @events << e }
+    sleep 0.2
+  end
+end
@@ -0,0 +1,51 @@
+# frozen_string_literal: true
+
+require "test_helper"
+require "tmpdir"
+require "fileutils"
+
+class Watchcat::AtomicSavesTest < Minitest::Test
+  def setup
+    @tmpdir = Dir.mktmpdir("watchcat")
+    @events = Queue.new
+  end
+
+  def teardown
+    @watchcat&.stop
+    FileUtils.remove_entry_secure(@tmpdir)
+  end
+
+  def test_a_file_written_continuously_is_not_held_forever
+    @watchcat = Watchcat.watch(@tmpdir, atomic_saves: true, filters: { ignore_access: true }) { |e| @events << e }
+    sleep 0.2
+
+    path = File.join(@tmpdir, "app.log")
+    events = []
+    File.open(path, "w") do |f|
+      # Well past the longest events are held, without ever pausing long
+      # enough for them to be released otherwise.
+      30.times do
+        f.write("line\n")
+        f.flush
+        sleep 0.05
+      end
+      events = received
+    end
+
+    assert events.any? { |e| e.paths == [path] }
+  end
+
+  def test_a_file_created_and_removed_is_reported
+    @watchcat = Watchcat.watch(@tmpdir, atomic_saves: true, filters: { ignore_access: true }) { |e| @events << e }
+    sleep 0.2
+
+    path = File.join(@tmpdir, "app.lock")
+    File.write(path, "")
+    File.delete(path)
+    sleep 0.5
+    events = received
+
+    assert events.any? { |e| e.kind.create? && e.paths == [path] }
+    assert events.any? { |e| e.kind.remove? && e.paths == [path] }
+  end
+end
@@ -86,6 +86,44 @@ class Watchcat::CLITest < Minitest::Test
     end
   end
 
+  def test_ignore_hidden_and_editor_temp_config
+    Dir.mktmpdir do |tmpdir|
+      config_file = File.join(tmpdir, "test_config.yml")
+
+      config_content = <<~YAML
+        watches:
+          - path: "#{tmpdir}"
+            ignore_hidden: true
+            ignore_editor_temp: true
+      YAML
+
+      File.write(config_file, config_content)
+
+      config = Watchcat::CLI::Config.load(config_file)
+      assert_equal true, config.watches.first[:ignore_hidden]
+      assert_equal true, config.watches.first[:ignore_editor_temp]
+    end
+  end
+
+  def test_allow_missing_config
+    Dir.mktmpdir do |tmpdir|
+      config_file = File.join(tmpdir, "test_config.yml")
+
+      config_content = <<~YAML
+        watches:
+          - path: "#{tmpdir}/dist"
+            allow_missing: true
+          - path: "#{tmpdir}"
+      YAML
+
+      File.write(config_file, config_content)
+
+      config = Watchcat::CLI::Config.load(config_file)
+      assert_equal true, config.watches.first[:allow_missing]
+      assert_equal false, config.watches.last[:allow_missing]
+    end
+  end
+
   def test_generate_template
     Dir.mktmpdir do |tmpdir|
       config_file = File.join(tmpdir, "template_config.yml")
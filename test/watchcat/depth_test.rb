@@ -0,0 +1,72 @@
+# frozen_string_literal: true
+
+require "test_helper"
+require "tmpdir"
+require "fileutils"
+
+class Watchcat::DepthTest < Minitest::Test
+  def setup
+    @tmpdir = Dir.mktmpdir("watchcat")
+    @tmpdir2 = Dir.mktmpdir("watchcat")
+    sleep 0.2
+  end
+
+  def teardown
+    @watchcat&.stop
+    FileUtils.remove_entry_secure(@tmpdir)
+    FileUtils.remove_entry_secure(@tmpdir2)
+  end
+
+  def test_events_below_depth_are_dropped
+    FileUtils.mkdir_p(File.join(@tmpdir, "a", "b", "c"))
+    events = []
+    @watchcat = Watchcat.watch(@tmpdir, depth: 2) { |e| events << e }
+    sleep 0.2
+
+    FileUtils.touch(File.join(@tmpdir, "a", "one.txt"))
+    FileUtils.touch(File.join(@tmpdir, "a", "b", "two.txt"))
+    FileUtils.touch(File.join(@tmpdir, "a", "b", "c", "three.txt"))
+    sleep 0.3
+
+    paths = events.flat_map(&:paths)
+    assert paths.any? { |p| p.end_with?("one.txt") }, inspect_events(events)
+    refute paths.any? { |p| p.end_with?("two.txt") }, inspect_events(events)
+    refute paths.any? { |p| p.end_with?("three.txt") }, inspect_events(events)
+  end
+
+  def test_directory_created_within_depth_is_watched
+    events = []
+    @watchcat = Watchcat.watch(@tmpdir, depth: 2) { |e| events << e }
+    sleep 0.2
+
+    sub_dir = FileUtils.mkdir(File.join(@tmpdir, "a")).first
+    sleep 0.3
+    FileUtils.touch(File.join(sub_dir, "one.txt"))
+    FileUtils.mkdir(File.join(sub_dir, "b"))
+    sleep 0.3
+    FileUtils.touch(File.join(sub_dir, "b", "two.txt"))
+    sleep 0.3
+
+    paths = events.flat_map(&:paths)
+    assert paths.any? { |p| p.end_with?("one.txt") }, inspect_events(events)
+    refute paths.any? { |p| p.end_with?("two.txt") }, inspect_events(events)
+  end
+
+  def test_watch_with_depth
+    FileUtils.mkdir_p(File.join(@tmpdir2, "a", "b"))
+    events = []
+    @watchcat = Watchcat.watch(@tmpdir, recursive: false) { |e| events << e }
+    sleep 0.2
+
+    @watchcat.watch(@tmpdir2, recursive: true, depth: 1)
+    sleep 0.2
+
+    FileUtils.touch(File.join(@tmpdir2, "one.txt"))
+    FileUtils.touch(File.join(@tmpdir2, "a", "two.txt"))
+    sleep 0.3
+
+    paths = events.flat_map(&:paths)
+    assert paths.any? { |p| p.end_with?("one.txt") }, inspect_events(events)
+    refute paths.any? { |p| p.end_with?("two.txt") }, inspect_events(events)
+  end
+end
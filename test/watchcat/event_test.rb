@@ -0,0 +1,120 @@
+# frozen_string_literal: true
+
+require "test_helper"
+require "tmpdir"
+require "fileutils"
+require "json"
+
+class Watchcat::EventTest < Minitest::Test
+  def rename_event
+    Watchcat::Event.new(
+      %w[modify rename both],
+      ["/app/a.rb", "/app/b.rb"],
+      "Modify(Name(Both))",
+      root: "/app",
+      relative_paths: ["a.rb", "b.rb"],
+      attributes: { tracker: 3 },
+      time: 1_700_000_000.25
+    )
+  end
+
+  def test_to_h
+    h = rename_event.to_h
+
+    assert_equal %w[modify rename both], h[:kind]
+    assert_equal "modify", h[:event]
+    assert_equal ["/app/a.rb", "/app/b.rb"], h[:paths]
+    assert_equal "/app/a.rb", h[:src_path]
+    assert_equal "/app/b.rb", h[:dest_path]
+    assert_equal "Modify(Name(Both))", h[:raw_kind]
+    assert_equal "/app", h[:root]
+    assert_equal ["a.rb", "b.rb"], h[:relative_paths]
+    assert_equal ["/app/a.rb", "/app/b.rb"], h[:resolved_paths]
+    assert_equal({ tracker: 3, flag: nil, info: nil, source: nil, process_id: nil }, h[:attributes])
+    assert_nil h[:normalized_kind]
+    assert_nil h[:directory]
+    assert_equal 1_700_000_000.25, h[:time]
+  end
+
+  def test_json_round_trip
+    event = Watchcat::Event.from_h(JSON.parse(rename_event.to_json))
+
+    assert event.kind.modify.rename.both?
+    assert_equal "/app/b.rb", event.dest_path
+    assert_equal 3, event.attributes.tracker
+    assert_equal Time.at(1_700_000_000.25), event.time
+    assert_equal rename_event.to_h, event.to_h
+  end
+
+  def test_round_trip_of_a_normalized_event
+    event = Watchcat::Event.new(%w[create folder], ["/app/lib"], "Create(Folder)", normalized: "created", directory: true)
+
+    copy = Watchcat::Event.from_h(event.to_h)
+
+    assert_equal :created, copy.normalized_kind
+    assert copy.directory?
+    assert_equal event.to_h, copy.to_h
+  end
+
+  def test_pid_and_process_name
+    event = Watchcat::Event.new(%w[modify data any], ["/app/a.rb"], "Modify(Data(Any))", attributes: { process_id: 42 }, process_name: "vim")
+
+    assert_equal 42, event.pid
+    assert_equal "vim", event.process_name
+    assert_equal 42, event.to_h[:pid]
+    assert_equal "vim", Watchcat::Event.from_h(JSON.parse(event.to_json)).process_name
+  end
+
+  def test_root_removed_and_restored
+    removed = Watchcat::Event.new(["other"], ["/app/dist"], "Other", attributes: { info: "root_removed" })
+    restored = Watchcat::Event.new(["other"], ["/app/dist"], "Other", attributes: { info: "root_restored" })
+
+    assert removed.root_removed?
+    refute removed.root_restored?
+    assert restored.root_restored?
+    refute Watchcat::Event.new(%w[remove folder], ["/app/dist"], "Remove(Folder)").root_removed?
+  end
+
+  def test_stat
+    stats = [{ size: 5, mtime: 1_700_000_000.5, mode: 0o100644, ino: 7, type: "file" }, nil]
+    event = Watchcat::Event.new(%w[modify rename both], ["/app/a.rb", "/app/b.rb"], "Modify(Name(Both))", stats: stats)
+
+    assert event.stat.file?
+    assert_equal Time.at(1_700_000_000.5), event.stat.mtime
+    assert_nil event.stats[1]
+    refute event.directory?
+    assert_equal stats, event.to_h[:stats]
+    assert_equal event.to_h, Watchcat::Event.from_h(JSON.parse(event.to_json)).to_h
+  end
+
+  def test_diff
+    diff = { unified: "--- a.css\n+++ a.css\n@@ -1 +1 @@\n-a\n+b\n", hunks: [[[1, 2], [1, 2]]] }
+    event = Watchcat::Event.new(%w[modify data any], ["/app/a.css"], "Modify(Data(Any))", diff: diff)
+
+    assert_equal [[1...2, 1...2]], event.diff.hunks
+    assert_equal diff, event.to_h[:diff]
+    assert_equal event.to_h, Watchcat::Event.from_h(JSON.parse(event.to_json)).to_h
+  end
+
+  def test_any
+    h = Watchcat::Event.new([], [], "Any").to_h
+
+    assert_equal ["any"], h[:kind]
+    assert_equal "any", h[:event]
+  end
+
+  def test_reported_events_have_a_time
+    Dir.mktmpdir("watchcat") do |tmpdir|
+      events = []
+      watcher = Watchcat.watch(tmpdir) { |e| events << e }
+      sleep 0.2
+      before = Time.now
+      File.write(File.join(tmpdir, "a.txt"), "a")
+      sleep 0.3
+      watcher.stop
+
+      refute_empty events
+      assert_in_delta before.to_f, events.first.time.to_f, 1
+    end
+  end
+end
@@ -0,0 +1,76 @@
+# frozen_string_literal: true
+
+require "test_helper"
+require "tmpdir"
+require "fileutils"
+
+# The process attribution needs Linux and CAP_SYS_ADMIN (e.g. a container run
+# with `--cap-add SYS_ADMIN` or `--privileged`); elsewhere only the fallback
+# is tested.
+class Watchcat::FanotifyTest < Minitest::Test
+  def setup
+    @tmpdir = Dir.mktmpdir("watchcat")
+    @events = Queue.new
+  end
+
+  def teardown
+    @watcher&.stop
+    FileUtils.remove_entry_secure(@tmpdir)
+  end
+
+  def test_writes_report_the_process
+    skip "fanotify needs Linux and CAP_SYS_ADMIN" unless Watchcat.fanotify_available?
+
+    @watcher = Watchcat.watch(@tmpdir, backend: :fanotify) { |e| @events << e }
+    sleep 0.2
+    File.write(File.join(@tmpdir, "a.txt"), "a")
+    pid = spawn("sh", "-c", "echo b >> #{File.join(@tmpdir, "a.txt")}; sleep 0.5")
+
+    Process.wait(pid)
+    writes = received.select { |e| e.kind.modify? && e.kind.modify.data_change? }
+
+    assert_includes writes.map(&:pid), Process.pid
+    assert_includes writes.map(&:pid), pid
+    assert_equal "sh", writes.find { |e| e.pid == pid }.process_name
+    assert writes.all? { |e| e.paths == [File.join(@tmpdir, "a.txt")] }
+  end
+
+  def test_writes_outside_the_watched_paths_are_not_reported
+    skip "fanotify needs Linux and CAP_SYS_ADMIN" unless Watchcat.fanotify_available?
+
+    FileUtils.mkdir(File.join(@tmpdir, "watched"))
+    @watcher = Watchcat.watch(File.join(@tmpdir, "watched"), backend: :fanotify) { |e| @events << e }
+    sleep 0.2
+    File.write(File.join(@tmpdir, "outside.txt"), "a")
+    sleep 0.5
+
+    assert_empty received
+  end
+
+  def test_falls_back_to_the_native_backend
+    skip "fanotify is available" if Watchcat.fanotify_available?
+
+    @watcher = Watchcat.watch(@tmpdir, backend: :fanotify) { |e| @events << e }
+    sleep 0.2
+    File.write(File.join(@tmpdir, "a.txt"), "a")
+    sleep 0.5
+
+    event = received.find { |e| e.kind.create? }
+
+    refute_nil event
+    assert_nil event.pid
+    assert_nil event.process_name
+  end
+
+  def test_force_polling_is_rejected
+    error = assert_raises(ArgumentError) do
+      @watcher = Watchcat.watch(@tmpdir, backend: :fanotify, force_polling: true) { |e| @events << e }
+    end
+    assert_equal "backend: :fanotify can't be used with force_polling: true", error.message
+
+    watcher = Watchcat::Watcher.new
+    assert_raises(ArgumentError) { watcher.start([@tmpdir], backend: "fanotify", force_polling: true) }
+  ensure
+    watcher&.close
+  end
+end
@@ -0,0 +1,118 @@
+# frozen_string_literal: true
+
+require "test_helper"
+require "tmpdir"
+require "fileutils"
+
+class Watchcat::FiberTest < Minitest::Test
+  # Just enough of a Fiber scheduler to run the watcher: fibers waiting on an
+  # IO or sleeping are resumed from an `IO.select` loop when the thread ends.
+  class Scheduler
+    def initialize
+      @readable = {}
+      @sleeping = {}
+    end
+
+    def fiber(&block)
+      fiber = Fiber.new(blocking: false, &block)
+      fiber.resume
+      fiber
+    end
+
+    def io_wait(io, events, _timeout)
+      @readable[io] = Fiber.current
+      Fiber.yield
+      events
+    end
+
+    def kernel_sleep(duration = nil)
+      @sleeping[Fiber.current] = now + (duration || 0)
+      Fiber.yield
+    end
+
+    def block(_blocker, timeout = nil)
+      kernel_sleep(timeout || 0)
+    end
+
+    def unblock(_blocker, _fiber); end
+
+    def close
+      until @readable.empty? && @sleeping.empty?
+        timeout = @sleeping.values.min&.then { |at| [at - now, 0].max }
+        readable, = IO.select(@readable.keys, nil, nil, timeout)
+        Array(readable).each { |io| @readable.delete(io).resume }
+
+        due = @sleeping.select { |_, at| at <= now }.keys
+        due.each { |fiber| @sleeping.delete(fiber).resume }
+      end
+    end
+
+    private
+
+    def now
+      Process.clock_gettime(Process::CLOCK_MONOTONIC)
+    end
+  end
+
+  def setup
+    skip if windows?
+
+    @tmpdir = Dir.mktmpdir("watchcat")
+  end
+
+  def teardown
+    FileUtils.remove_entry_secure(@tmpdir) if @tmpdir
+  end
+
+  def test_events_are_delivered_on_the_scheduler_thread
+    events = []
+    threads = []
+
+    Thread.new do
+      Fiber.set_scheduler(Scheduler.new)
+      Fiber.schedule do
+        watcher = Watchcat.watch(@tmpdir, fiber: true) do |e|
+          events << File.basename(e.paths.first)
+          threads << Thread.current
+        end
+        sleep 0.2
+        File.write(File.join(@tmpdir, "a.txt"), "")
+        sleep 0.3
+
+        assert watcher.alive?
+        watcher.stop
+      end
+      threads << Thread.current
+    end.join(5)
+
+    assert_includes events, "a.txt"
+    assert_equal 1, threads.uniq.count
+  end
+
+  def test_watcher_is_not_alive_after_stop
+    watcher = nil
+
+    Thread.new do
+      Fiber.set_scheduler(Scheduler.new)
+      Fiber.schedule do
+        watcher = Watchcat.watch(@tmpdir, fiber: true) { |_| }
+        sleep 0.1
+        watcher.stop
+      end
+    end.join(5)
+
+    refute watcher.alive?
+  end
+
+  def test_fiber_requires_a_scheduler
+    error = assert_raises(ArgumentError) { Watchcat.watch(@tmpdir, fiber: true) { |_| } }
+
+    assert_equal "fiber: true requires a Fiber scheduler", error.message
+  end
+
+  def test_drain_events_before_start
+    error = assert_raises(RuntimeError) { Watchcat::Watcher.new.drain_events }
+
+    assert_equal "watcher not started", error.message
+  end
+end
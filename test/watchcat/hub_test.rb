@@ -0,0 +1,138 @@
+# frozen_string_literal: true
+
+require "test_helper"
+require "tmpdir"
+require "fileutils"
+
+class Watchcat::HubTest < Minitest::Test
+  def setup
+    @tmpdir = Dir.mktmpdir("watchcat")
+    @a = File.join(@tmpdir, "a")
+    @b = File.join(@tmpdir, "b")
+    FileUtils.mkdir_p([@a, @b])
+    @hub = Watchcat::Hub.new
+  end
+
+  def teardown
+    @hub.stop
+    FileUtils.remove_entry_secure(@tmpdir)
+  end
+
+  def test_events_are_routed_by_path
+    a_events = []
+    b_events = []
+    @hub.subscribe(@a) { |e| a_events << File.basename(e.paths.first) }
+    @hub.subscribe(@b) { |e| b_events << File.basename(e.paths.first) }
+    sleep 0.2
+
+    FileUtils.touch(File.join(@a, "a.txt"))
+    sleep 0.3
+
+    assert_includes a_events, "a.txt"
+    assert_empty b_events
+  end
+
+  def test_subscriptions_share_one_thread
+    threads = -> { Thread.list.count { |t| t.name == "watchcat-watcher" && t.alive? } }
+    before = threads.call
+
+    @hub.subscribe(@a) { |_e| }
+    @hub.subscribe(@b) { |_e| }
+    sleep 0.2
+
+    assert_equal 1, threads.call - before
+    assert @hub.alive?
+  end
+
+  def test_overlapping_subscriptions_both_receive_events
+    outer = []
+    inner = []
+    @hub.subscribe(@tmpdir) { |e| outer << File.basename(e.paths.first) }
+    @hub.subscribe(@a) { |e| inner << File.basename(e.paths.first) }
+    sleep 0.2
+
+    FileUtils.touch(File.join(@a, "a.txt"))
+    sleep 0.3
+
+    assert_includes outer, "a.txt"
+    assert_includes inner, "a.txt"
+  end
+
+  def test_cancelling_the_inner_of_overlapping_subscriptions
+    outer = []
+    @hub.subscribe(@tmpdir) { |e| outer << File.basename(e.paths.first) }
+    inner = @hub.subscribe(@a) { |_e| }
+    sleep 0.2
+
+    inner.cancel
+    sleep 0.2
+    FileUtils.touch(File.join(@a, "a.txt"))
+    sleep 0.3
+
+    assert_includes outer, "a.txt"
+  end
+
+  def test_cancelling_the_outer_of_overlapping_subscriptions
+    inner = []
+    outer = @hub.subscribe(@tmpdir) { |_e| }
+    @hub.subscribe(@a) { |e| inner << File.basename(e.paths.first) }
+    sleep 0.2
+
+    outer.cancel
+    sleep 0.2
+    FileUtils.touch(File.join(@a, "a.txt"))
+    sleep 0.3
+
+    assert_includes inner, "a.txt"
+  end
+
+  def test_non_recursive_subscription_skips_nested_events
+    recursive = []
+    flat = []
+    @hub.subscribe(@tmpdir, recursive: true) { |e| recursive << File.basename(e.paths.first) }
+    @hub.subscribe(@tmpdir, recursive: false) { |e| flat << File.basename(e.paths.first) }
+    sleep 0.2
+
+    FileUtils.touch(File.join(@a, "nested.txt"))
+    FileUtils.touch(File.join(@tmpdir, "top.txt"))
+    sleep 0.3
+
+    assert_includes recursive, "nested.txt"
+    assert_includes flat, "top.txt"
+    refute_includes flat, "nested.txt"
+  end
+
+  def test_cancelled_subscription_receives_nothing
+    events = []
+    subscription = @hub.subscribe(@a) { |e| events << e }
+    sleep 0.2
+
+    subscription.cancel
+    assert_empty @hub.subscriptions
+    sleep 0.2
+    FileUtils.touch(File.join(@a, "a.txt"))
+    sleep 0.3
+
+    assert_empty events
+  end
+
+  def test_subscription_options
+    events = []
+    @hub.subscribe(@a, patterns: ["*.rb"], filters: { ignore_remove: true }) { |e| events << e }
+    sleep 0.2
+
+    FileUtils.touch(File.join(@a, "a.txt"))
+    FileUtils.touch(File.join(@a, "a.rb"))
+    File.delete(File.join(@a, "a.rb"))
+    sleep 0.3
+
+    refute_empty events
+    assert(events.all? { |e| File.basename(e.paths.first) == "a.rb" }, inspect_events(events))
+    assert(events.none? { |e| e.kind.remove? }, inspect_events(events))
+  end
+
+  def test_subscribe_requires_an_existing_path
+    assert_raises(ArgumentError) { @hub.subscribe(File.join(@tmpdir, "missing")) { |_e| } }
+    assert_empty @hub.subscriptions
+  end
+end
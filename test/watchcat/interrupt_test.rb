@@ -0,0 +1,67 @@
+# frozen_string_literal: true
+
+require "test_helper"
+require "tmpdir"
+require "fileutils"
+require "timeout"
+
+class Watchcat::InterruptTest < Minitest::Test
+  def setup
+    @tmpdir = Dir.mktmpdir("watchcat")
+    @watcher = Watchcat::Watcher.new
+  end
+
+  def teardown
+    @watcher.close
+    FileUtils.remove_entry_secure(@tmpdir)
+  end
+
+  def test_thread_kill_stops_a_blocked_watch
+    thread = Thread.new { @watcher.watch([@tmpdir], recursive: true) { |*| } }
+    sleep 0.2
+
+    thread.kill
+
+    assert thread.join(1), "watch wasn't interrupted"
+  end
+
+  def test_thread_raise_is_raised_from_watch
+    thread = Thread.new { @watcher.watch([@tmpdir], recursive: true) { |*| } }
+    thread.report_on_exception = false
+    sleep 0.2
+
+    thread.raise(RuntimeError, "interrupted")
+
+    assert thread.join(1), "watch wasn't interrupted"
+    error = assert_raises(RuntimeError) { thread.value }
+    assert_equal "interrupted", error.message
+  end
+
+  def test_timeout
+    assert_raises(Timeout::Error) do
+      Timeout.timeout(0.3) { @watcher.watch([@tmpdir], recursive: true) { |*| } }
+    end
+  end
+
+  def test_events_are_still_delivered_after_an_interrupt_is_handled
+    events = []
+    thread = Thread.new do
+      Thread.handle_interrupt(RuntimeError => :never) do
+        @watcher.watch([@tmpdir], recursive: true) { |_, paths| events << File.basename(paths.first) }
+      end
+    end
+    thread.report_on_exception = false
+    sleep 0.2
+
+    # Deferred by `handle_interrupt`, so the watch has to carry on.
+    thread.raise(RuntimeError, "deferred")
+    sleep 0.1
+    File.write(File.join(@tmpdir, "a.txt"), "")
+    sleep 0.3
+
+    assert thread.alive?
+    assert_includes events, "a.txt"
+  ensure
+    thread&.kill
+  end
+end
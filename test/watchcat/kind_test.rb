@@ -205,6 +205,50 @@ class Watchcat::KindTest < Minitest::Test
     end
   end
 
+  def test_mv_file_attributes
+    skip unless RUBY_PLATFORM.match?("linux")
+
+    file = FileUtils.touch(File.join(@tmpdir, "a.txt"))[0]
+
+    events = []
+    sleep 0.2
+    @watchcat = Watchcat.watch(@tmpdir, recursive: false) { |e| events << e }
+    sleep 0.2
+    File.rename(file, File.join(@tmpdir, "b.txt"))
+    sleep 0.2
+
+    assert_equal 3, events.count, inspect_events(events)
+    trackers = events.map { |e| e.attributes.tracker }
+    refute_nil trackers.first
+    assert_equal [trackers.first] * 3, trackers
+  end
+
+  def test_any_and_other_kinds
+    any = Watchcat::Event.new(["any"], [@tmpdir], "Any")
+    assert any.kind.any?
+    refute any.kind.other?
+    assert_equal "any", any.event
+    assert_equal "any", any.kind.event_type
+    capture_io do
+      refute any.kind.any.file?
+      refute any.kind.any.folder?
+    end
+
+    other = Watchcat::Event.new(["other"], [@tmpdir], "Other", attributes: { info: "mount" })
+    assert other.kind.other?
+    refute other.kind.any?
+    assert_equal "other", other.kind.event_type
+    assert_equal "mount", other.attributes.info
+  end
+
+  def test_attributes_default_to_nil
+    event = Watchcat::Event.new(["create", "file"], [File.join(@tmpdir, "a.txt")], "create")
+
+    assert_equal Watchcat::EventAttributes.new, event.attributes
+    assert_nil event.attributes.tracker
+    assert_nil event.attributes.process_id
+  end
+
   def test_write_to_file
     file = FileUtils.touch(File.join(@tmpdir, "a.txt"))[0]
 
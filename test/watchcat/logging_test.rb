@@ -0,0 +1,95 @@
+# frozen_string_literal: true
+
+require "test_helper"
+require "tmpdir"
+require "fileutils"
+require "logger"
+require "stringio"
+require "rbconfig"
+
+class Watchcat::LoggingTest < Minitest::Test
+  def setup
+    @tmpdir = Dir.mktmpdir("watchcat")
+    @log = StringIO.new
+    Watchcat.logger = Logger.new(@log, level: :debug)
+  end
+
+  def teardown
+    Watchcat.logger = nil
+    FileUtils.remove_entry_secure(@tmpdir)
+  end
+
+  def test_logs_what_is_watched_and_the_events
+    watch { File.write(File.join(@tmpdir, "a.txt"), "a") }
+
+    assert_match(/watching path=#{Regexp.escape(@tmpdir)} backend=\w+ recursive=true/, @log.string)
+    assert_match(/DEBUG -- watchcat: event kind=Create\(File\) paths=#{Regexp.escape(File.join(@tmpdir, "a.txt"))}/, @log.string) unless mac_os?
+    assert_match(/closed/, @log.string)
+  end
+
+  def test_logs_events_dropped_by_the_filters
+    watch(filters: { ignore_create: true }) { File.write(File.join(@tmpdir, "a.txt"), "a") }
+
+    assert_match(/event dropped reason=kind kind=Create/, @log.string)
+  end
+
+  def test_logs_events_dropped_by_the_patterns
+    watch(patterns: ["*.rb"]) { File.write(File.join(@tmpdir, "a.txt"), "a") }
+
+    assert_match(/event dropped reason=patterns paths=.*a\.txt/, @log.string)
+  end
+
+  def test_logs_added_paths
+    sub = File.join(@tmpdir, "sub")
+    FileUtils.mkdir(sub)
+
+    watch(recursive: false) { |w| w.watch(sub) }
+
+    assert_match(/command command=Watch/, @log.string)
+    assert_match(/watching path=#{Regexp.escape(sub)} recursive=false/, @log.string)
+  end
+
+  def test_only_records_at_the_logger_level
+    Watchcat.logger = Logger.new(@log, level: :info)
+
+    watch { File.write(File.join(@tmpdir, "a.txt"), "a") }
+
+    assert_match(/INFO -- watchcat: watching/, @log.string)
+    refute_match(/DEBUG/, @log.string)
+  end
+
+  def test_nil_turns_logging_off
+    Watchcat.logger = nil
+
+    watch { File.write(File.join(@tmpdir, "a.txt"), "a") }
+
+    assert_empty @log.string
+  end
+
+  def test_watchcat_log
+    lib = File.expand_path("../../lib", __dir__)
+    script = <<~RUBY
+      w = Watchcat.watch(#{@tmpdir.dump}) { |_| }
+      sleep 0.2
+      w.stop
+    RUBY
+
+    _, err = capture_subprocess_io do
+      system({ "WATCHCAT_LOG" => "info" }, RbConfig.ruby, "-I", lib, "-rwatchcat", "-e", script)
+    end
+
+    assert_match(/INFO -- watchcat: watching path=#{Regexp.escape(@tmpdir)}/, err)
+  end
+
+  private
+
+  def watch(**options)
+    watcher = Watchcat.watch(@tmpdir, **options) { |_| }
+    sleep 0.2
+    yield watcher
+    sleep 0.3
+    watcher.stop
+    sleep 0.1
+    Watchcat::Logging.flush
+  end
+end
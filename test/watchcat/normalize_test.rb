@@ -0,0 +1,147 @@
+# frozen_string_literal: true
+
+require "test_helper"
+require "tmpdir"
+require "fileutils"
+
+# Runs the same filesystem operations under inotify and under polling and
+# checks that `normalize: true` reports the same changes for both.
+class Watchcat::NormalizeTest < Minitest::Test
+  STEPS = {
+    "create file" => ->(dir) { File.write(File.join(dir, "a.txt"), "hello") },
+    "write file" => ->(dir) { File.write(File.join(dir, "a.txt"), "hello world") },
+    "rename file" => ->(dir) { File.rename(File.join(dir, "a.txt"), File.join(dir, "b.txt")) },
+    "create tree" => lambda { |dir|
+      FileUtils.mkdir_p(File.join(dir, "n", "m"))
+      File.write(File.join(dir, "n", "m", "x.txt"), "x")
+    },
+    "rename directory" => ->(dir) { File.rename(File.join(dir, "old"), File.join(dir, "new")) },
+    "remove file" => ->(dir) { File.delete(File.join(dir, "b.txt")) },
+    "remove tree" => ->(dir) { FileUtils.rm_r(File.join(dir, "n")) },
+    "create and remove file" => lambda { |dir|
+      File.write(File.join(dir, "t.txt"), "t")
+      File.delete(File.join(dir, "t.txt"))
+    },
+  }.freeze
+
+  EXPECTED = {
+    "create file" => [[:created, ["a.txt"], false]],
+    "write file" => [[:modified, ["a.txt"], false]],
+    "rename file" => [[:moved, ["a.txt", "b.txt"], false]],
+    "create tree" => [[:created, ["n"], true], [:created, ["n/m"], true], [:created, ["n/m/x.txt"], false]],
+    "rename directory" => [[:moved, ["old", "new"], true]],
+    "remove file" => [[:removed, ["b.txt"], false]],
+    "remove tree" => [[:removed, ["n/m/x.txt"], false], [:removed, ["n/m"], true], [:removed, ["n"], true]],
+    "create and remove file" => [],
+  }.freeze
+
+  def setup
+    skip unless RUBY_PLATFORM.match?("linux")
+
+    @tmpdirs = []
+  end
+
+  def teardown
+    @watchcat&.stop
+    @tmpdirs&.each { |dir| FileUtils.remove_entry_secure(dir) }
+  end
+
+  def test_native_backend_produces_normalized_stream
+    assert_equal EXPECTED, run_steps(force_polling: false)
+  end
+
+  def test_polling_produces_normalized_stream
+    assert_equal EXPECTED, run_steps(force_polling: true)
+  end
+
+  def test_native_and_polling_streams_are_the_same
+    assert_equal run_steps(force_polling: false), run_steps(force_polling: true)
+  end
+
+  def test_chmod_is_metadata_changed
+    dir = make_tmpdir
+    file = File.join(dir, "a.txt")
+    File.write(file, "a")
+    events = []
+    @watchcat = Watchcat.watch(dir, normalize: true) { |e| events << e }
+    sleep 0.2
+
+    File.chmod(0o600, file)
+    sleep 0.5
+
+    assert_equal [:metadata_changed], events.map(&:normalized_kind), inspect_events(events)
+    refute events.first.directory?
+  end
+
+  def test_moved_event_has_src_and_dest_path
+    dir = make_tmpdir
+    FileUtils.mkdir(File.join(dir, "old"))
+    events = []
+    @watchcat = Watchcat.watch(dir, normalize: true) { |e| events << e }
+    sleep 0.2
+
+    File.rename(File.join(dir, "old"), File.join(dir, "new"))
+    sleep 0.5
+
+    assert_equal 1, events.count, inspect_events(events)
+    event = events.first
+    assert_equal :moved, event.normalized_kind
+    assert_equal File.join(dir, "old"), event.src_path
+    assert_equal File.join(dir, "new"), event.dest_path
+    assert event.directory?
+  end
+
+  def test_paths_are_reported_under_a_symlinked_root_as_given
+    dir = make_tmpdir
+    FileUtils.mkdir(File.join(dir, "real"))
+    File.symlink(File.join(dir, "real"), File.join(dir, "link"))
+    File.write(File.join(dir, "real", "a.txt"), "a")
+    events = []
+    @watchcat = Dir.chdir(dir) { Watchcat.watch("link", normalize: true) { |e| events << e } }
+    sleep 0.2
+
+    File.write(File.join(dir, "real", "a.txt"), "aa")
+    sleep 0.5
+
+    assert_equal [[:modified, ["link/a.txt"]]], events.map { |e| [e.normalized_kind, e.paths] }, inspect_events(events)
+  end
+
+  def test_normalized_kind_is_nil_without_normalize
+    dir = make_tmpdir
+    events = []
+    @watchcat = Watchcat.watch(dir) { |e| events << e }
+    sleep 0.2
+
+    FileUtils.touch(File.join(dir, "a.txt"))
+    sleep 0.3
+
+    refute_empty events
+    events.each { |event| assert_nil event.normalized_kind }
+  end
+
+  private
+
+  def make_tmpdir
+    Dir.mktmpdir("watchcat").tap { |dir| @tmpdirs << dir }
+  end
+
+  def run_steps(force_polling:)
+    dir = make_tmpdir
+    File.write(File.join(dir, "keep.txt"), "k")
+    FileUtils.mkdir_p(File.join(dir, "old", "sub"))
+    File.write(File.join(dir, "old", "sub", "f.txt"), "f")
+
+    events = []
+    watcher = Watchcat.watch(dir, normalize: true, force_polling: force_polling, poll_interval: 50) { |e| events << e }
+    sleep 0.3
+
+    STEPS.to_h do |name, step|
+      events.clear
+      step.call(dir)
+      sleep 0.5
+      [name, events.map { |e| [e.normalized_kind, e.relative_paths, e.directory?] }]
+    end
+  ensure
+    watcher&.stop
+  end
+end
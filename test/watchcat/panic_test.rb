@@ -0,0 +1,41 @@
+# frozen_string_literal: true
+
+require "test_helper"
+
+class Watchcat::PanicTest < Minitest::Test
+  def test_internal_error_is_a_standard_error
+    assert_operator Watchcat::InternalError, :<, StandardError
+  end
+
+  def test_panic_in_a_method_raises_internal_error
+    assert_panic_raises("method", "test panic")
+  end
+
+  def test_panic_without_the_gvl_raises_internal_error
+    assert_panic_raises("without_gvl", "test panic without the GVL")
+  end
+
+  def test_panic_with_the_gvl_raises_internal_error
+    assert_panic_raises("with_gvl", "test panic with the GVL")
+  end
+
+  def test_process_keeps_working_after_a_panic
+    assert_panic_raises("without_gvl", "test panic without the GVL")
+
+    watcher = Watchcat::Watcher.new
+    watcher.close
+    assert_panic_raises("without_gvl", "test panic without the GVL")
+  end
+
+  private
+
+  def assert_panic_raises(site, message)
+    error = nil
+    # The panic hook prints to stderr.
+    capture_subprocess_io do
+      error = assert_raises(Watchcat::InternalError) { Watchcat.__panic_for_test__(site) }
+    end
+
+    assert_equal "panic in watchcat: #{message}", error.message
+  end
+end
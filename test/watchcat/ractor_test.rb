@@ -0,0 +1,81 @@
+# frozen_string_literal: true
+
+require "test_helper"
+require "tmpdir"
+require "fileutils"
+require "timeout"
+
+class Watchcat::RactorTest < Minitest::Test
+  def setup
+    Warning[:experimental] = false
+    @tmpdir = Dir.mktmpdir("watchcat")
+    sleep 0.2
+  end
+
+  def teardown
+    @watchcat&.stop
+    FileUtils.remove_entry_secure(@tmpdir)
+  end
+
+  def test_events_are_sent_to_a_port_as_shareable_objects
+    port = new_port
+    @watchcat = Watchcat.watch(@tmpdir, port: port)
+    sleep 0.2
+
+    FileUtils.touch(File.join(@tmpdir, "a.txt"))
+    event = receive_from(port)
+
+    assert_kind_of Watchcat::Event, event
+    assert Ractor.shareable?(event)
+    assert event.frozen?
+    assert event.kind.frozen?
+    assert_equal "a.txt", File.basename(event.paths.first)
+  end
+
+  def test_events_go_to_both_the_block_and_the_port
+    port = new_port
+    events = []
+    @watchcat = Watchcat.watch(@tmpdir, port: port) { |e| events << e }
+    sleep 0.2
+
+    FileUtils.touch(File.join(@tmpdir, "a.txt"))
+    received = receive_from(port)
+    sleep 0.2
+
+    assert_includes events, received
+  end
+
+  def test_frozen_watcher_is_shareable
+    watcher = Ractor.make_shareable(Watchcat::Watcher.new)
+
+    assert Ractor.shareable?(watcher)
+  end
+
+  def test_watch_from_a_non_main_ractor
+    ractor = Ractor.new(@tmpdir) do |dir|
+      events = []
+      watcher = Watchcat.watch(dir) { |e| events << File.basename(e.paths.first) }
+      sleep 0.2
+      File.write(File.join(dir, "a.txt"), "")
+      sleep 0.3
+      watcher.stop
+      events
+    end
+
+    events = ractor.respond_to?(:value) ? ractor.value : ractor.take
+
+    assert_includes events, "a.txt"
+  end
+
+  private
+
+  # `Ractor::Port` only exists since Ruby 3.5. Before that, a Ractor itself
+  # is sent to.
+  def new_port
+    defined?(Ractor::Port) ? Ractor::Port.new : Ractor.current
+  end
+
+  def receive_from(port)
+    Timeout.timeout(2) { port.is_a?(Ractor) ? Ractor.receive : port.receive }
+  end
+end
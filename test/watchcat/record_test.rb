@@ -0,0 +1,136 @@
+# frozen_string_literal: true
+
+require "test_helper"
+require "tmpdir"
+require "fileutils"
+require "json"
+
+class Watchcat::RecordTest < Minitest::Test
+  def setup
+    @tmpdir = Dir.mktmpdir("watchcat")
+    @dir = File.join(@tmpdir, "watched")
+    FileUtils.mkdir(@dir)
+    @recording = File.join(@tmpdir, "events.jsonl")
+  end
+
+  def teardown
+    FileUtils.remove_entry_secure(@tmpdir)
+  end
+
+  def test_replay_delivers_the_recorded_events
+    events = record { File.write(File.join(@dir, "a.txt"), "a") }
+    replayed = []
+
+    count = Watchcat.replay(@recording, speed: nil) { |e| replayed << e }
+
+    assert_operator count, :>=, events.size
+    assert_equal inspect_events(events), inspect_events(replayed)
+    assert_equal events.map(&:relative_paths), replayed.map(&:relative_paths)
+  end
+
+  def test_recording_format
+    record(filters: { ignore_access: true }) { File.write(File.join(@dir, "a.txt"), "a") }
+
+    header, *records = File.readlines(@recording).map { |line| JSON.parse(line) }
+
+    assert_equal 1, header["watchcat"]
+    assert_equal [@dir], header["paths"]
+    assert header["options"]["ignore_access"]
+    refute_empty records
+    assert(records.all? { |r| r["t"].is_a?(Float) && r.key?("event") })
+  end
+
+  def test_replay_uses_the_recorded_options
+    record(filters: { ignore_create: true }) { File.write(File.join(@dir, "a.txt"), "a") }
+    replayed = []
+
+    Watchcat.replay(@recording, speed: nil) { |e| replayed << e }
+
+    assert(replayed.none? { |e| e.kind.create? })
+  end
+
+  def test_replay_options_can_be_overridden
+    record { File.write(File.join(@dir, "a.txt"), "a") }
+    replayed = []
+
+    Watchcat.replay(@recording, speed: nil, filters: { ignore_create: true }) { |e| replayed << e }
+
+    assert(replayed.none? { |e| e.kind.create? })
+  end
+
+  def test_replay_refuses_atomic_saves_and_normalize
+    record { File.write(File.join(@dir, "a.txt"), "a") }
+
+    error = assert_raises(ArgumentError) { Watchcat.replay(@recording, normalize: true) { |_| } }
+    assert_equal "normalize can't be used with replay", error.message
+    assert_raises(ArgumentError) { Watchcat.replay(@recording, atomic_saves: true) { |_| } }
+  end
+
+  def test_replay_of_a_recording_made_with_normalize_delivers_the_raw_events
+    record(normalize: true) { File.write(File.join(@dir, "a.txt"), "a") }
+    replayed = []
+
+    Watchcat.replay(@recording, speed: nil) { |e| replayed << e }
+
+    refute_empty replayed
+    assert(replayed.all? { |e| e.normalized_kind.nil? })
+  end
+
+  def test_replay_keeps_the_timing
+    record do
+      File.write(File.join(@dir, "a.txt"), "a")
+      sleep 0.5
+      File.write(File.join(@dir, "b.txt"), "b")
+    end
+    times = {}
+    started = Process.clock_gettime(Process::CLOCK_MONOTONIC)
+
+    Watchcat.replay(@recording, speed: 2.0, filters: { ignore_access: true, ignore_modify: true }) do |e|
+      times[File.basename(e.paths.first)] ||= Process.clock_gettime(Process::CLOCK_MONOTONIC) - started
+    end
+
+    assert_in_delta 0.25, times["b.txt"] - times["a.txt"], 0.1
+  end
+
+  def test_replay_goes_through_debounce
+    record { 3.times { |i| File.write(File.join(@dir, "a.txt"), i.to_s) } }
+    replayed = []
+
+    Watchcat.replay(@recording, speed: nil, debounce: 100) { |e| replayed << e }
+
+    assert_equal 1, replayed.size
+  end
+
+  def test_recording_inside_the_watched_directory
+    @recording = File.join(@dir, "events.jsonl")
+    events = record { File.write(File.join(@dir, "a.txt"), "a") }
+
+    refute_includes events.flat_map(&:paths), @recording
+    assert_operator File.readlines(@recording).size, :<, 50
+  end
+
+  def test_replay_of_something_else
+    File.write(@recording, "hello\n")
+
+    assert_raises(ArgumentError) { Watchcat.replay(@recording) { |_| } }
+    assert_raises(Errno::ENOENT) { Watchcat.replay(File.join(@tmpdir, "missing.jsonl")) { |_| } }
+  end
+
+  def test_replay_speed_must_be_positive
+    record { File.write(File.join(@dir, "a.txt"), "a") }
+
+    assert_raises(ArgumentError) { Watchcat.replay(@recording, speed: 0) { |_| } }
+  end
+
+  private
+
+  def record(**options)
+    events = []
+    watcher = Watchcat.watch(@dir, record_to: @recording, **options) { |e| events << e }
+    sleep 0.2
+    yield
+    sleep 0.5
+    watcher.stop
+    events
+  end
+end
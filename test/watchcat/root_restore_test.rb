@@ -0,0 +1,96 @@
+# frozen_string_literal: true
+
+require "test_helper"
+require "tmpdir"
+require "fileutils"
+
+class Watchcat::RootRestoreTest < Minitest::Test
+  def setup
+    @tmpdir = Dir.mktmpdir("watchcat")
+    @dist = File.join(@tmpdir, "dist")
+    FileUtils.mkdir_p(@dist)
+    @events = Queue.new
+    @watchcat = Watchcat.watch(@dist, filters: { ignore_access: true }) { |e| @events << e }
+    sleep 0.2
+  end
+
+  def teardown
+    @watchcat&.stop
+    FileUtils.remove_entry_secure(@tmpdir)
+  end
+
+  def test_reports_the_root_being_removed_and_restored
+    FileUtils.rm_rf(@dist)
+    sleep 0.3
+    assert received.any? { |e| e.root_removed? && e.paths == [@dist] }
+
+    FileUtils.mkdir_p(@dist)
+    sleep 0.3
+    assert received.any? { |e| e.root_restored? && e.paths == [@dist] }
+  end
+
+  def test_watches_the_root_again_once_restored
+    FileUtils.rm_rf(@dist)
+    FileUtils.mkdir_p(@dist)
+    sleep 0.3
+    received
+    File.write(File.join(@dist, "app.js"), "x")
+    sleep 0.5
+
+    assert received.any? { |e| e.kind.create? && e.paths == [File.join(@dist, "app.js")] }
+  end
+
+  def test_root_moved_away_and_back
+    File.rename(@dist, "#{@dist}.old")
+    sleep 0.3
+    File.rename("#{@dist}.old", @dist)
+    sleep 0.3
+    File.write(File.join(@dist, "app.js"), "x")
+    sleep 0.5
+
+    events = received
+    assert events.any?(&:root_removed?)
+    assert events.any?(&:root_restored?)
+    assert events.any? { |e| e.paths == [File.join(@dist, "app.js")] }
+  end
+
+  def test_relative_root_is_restored
+    @watchcat.stop
+    @watchcat = Dir.chdir(@tmpdir) do
+      Watchcat.watch("dist", filters: { ignore_access: true }) { |e| @events << e }
+    end
+    sleep 0.2
+
+    FileUtils.rm_rf(@dist)
+    sleep 0.3
+    FileUtils.mkdir_p(@dist)
+    sleep 0.3
+    File.write(File.join(@dist, "app.js"), "x")
+    sleep 0.5
+
+    events = received
+    assert events.any?(&:root_removed?)
+    assert events.any?(&:root_restored?)
+    assert events.any? { |e| File.basename(e.paths.first) == "app.js" && e.kind.create? }
+  end
+
+  def test_relative_root_is_reported_as_given
+    @watchcat.stop
+    @watchcat = Dir.chdir(@tmpdir) do
+      Watchcat.watch("dist", filters: { ignore_access: true }) { |e| @events << e }
+    end
+    sleep 0.2
+    assert_equal ["dist"], @watchcat.watched
+
+    FileUtils.rm_rf(@dist)
+    sleep 0.3
+    FileUtils.mkdir_p(@dist)
+    sleep 0.3
+    File.write(File.join(@dist, "app.js"), "x")
+    sleep 0.5
+
+    events = received
+    assert events.any? { |e| e.root_restored? && e.paths == ["dist"] }
+    assert events.any? { |e| e.root == "dist" && e.relative_paths == ["app.js"] }
+  end
+end
@@ -0,0 +1,73 @@
+# frozen_string_literal: true
+
+require "test_helper"
+require "tmpdir"
+require "fileutils"
+
+class Watchcat::RootTest < Minitest::Test
+  def setup
+    @tmpdir = Dir.mktmpdir("watchcat")
+    @tmpdir2 = Dir.mktmpdir("watchcat")
+    sleep 0.2
+  end
+
+  def teardown
+    @watchcat&.stop
+    FileUtils.remove_entry_secure(@tmpdir)
+    FileUtils.remove_entry_secure(@tmpdir2)
+  end
+
+  def test_event_reports_root_and_relative_paths
+    sub_dir = FileUtils.mkdir_p(File.join(@tmpdir, "a", "b")).first
+    events = []
+    @watchcat = Watchcat.watch(@tmpdir) { |e| events << e }
+    sleep 0.2
+
+    FileUtils.touch(File.join(sub_dir, "c.txt"))
+    sleep 0.3
+
+    event = events.find { |e| e.paths.any? { |p| p.end_with?("c.txt") } }
+    refute_nil event, inspect_events(events)
+    assert_equal @tmpdir, event.root
+    assert_equal [File.join("a", "b", "c.txt")], event.relative_paths
+  end
+
+  def test_overlapping_roots_resolve_to_the_longest_root
+    sub_dir = FileUtils.mkdir(File.join(@tmpdir, "sub")).first
+    events = []
+    @watchcat = Watchcat.watch([@tmpdir, sub_dir]) { |e| events << e }
+    sleep 0.2
+
+    FileUtils.touch(File.join(sub_dir, "a.txt"))
+    sleep 0.3
+
+    event = events.find { |e| e.paths.any? { |p| p.end_with?("a.txt") } }
+    refute_nil event, inspect_events(events)
+    assert_equal sub_dir, event.root
+    assert_equal ["a.txt"], event.relative_paths
+  end
+
+  def test_root_of_path_added_with_watch
+    events = []
+    @watchcat = Watchcat.watch(@tmpdir, recursive: false) { |e| events << e }
+    sleep 0.2
+
+    @watchcat.watch(@tmpdir2, recursive: false)
+    sleep 0.2
+
+    FileUtils.touch(File.join(@tmpdir2, "a.txt"))
+    sleep 0.3
+
+    event = events.find { |e| e.paths.any? { |p| p.include?(@tmpdir2) } }
+    refute_nil event, inspect_events(events)
+    assert_equal @tmpdir2, event.root
+    assert_equal ["a.txt"], event.relative_paths
+  end
+
+  def test_event_built_without_details_has_no_root
+    event = Watchcat::Event.new(["create", "file"], [File.join(@tmpdir, "a.txt")], "create")
+
+    assert_nil event.root
+    assert_equal [nil], event.relative_paths
+  end
+end
@@ -0,0 +1,80 @@
+# frozen_string_literal: true
+
+require "test_helper"
+require "tmpdir"
+require "fileutils"
+
+class Watchcat::StatTest < Minitest::Test
+  def setup
+    @tmpdir = Dir.mktmpdir("watchcat")
+    @events = Queue.new
+  end
+
+  def teardown
+    @watchcat&.stop
+    FileUtils.remove_entry_secure(@tmpdir)
+  end
+
+  def test_events_carry_the_stat_of_their_paths
+    @watchcat = Watchcat.watch(@tmpdir, stat: true) { |e| @events << e }
+    sleep 0.2
+    File.write(File.join(@tmpdir, "a.txt"), "hello")
+    FileUtils.mkdir(File.join(@tmpdir, "lib"))
+    sleep 0.5
+
+    events = received
+    file = events.find { |e| e.paths == [File.join(@tmpdir, "a.txt")] && e.stat&.size == 5 }
+    dir = events.find { |e| e.paths == [File.join(@tmpdir, "lib")] }
+
+    refute_nil file
+    assert file.stat.file?
+    assert_kind_of Time, file.stat.mtime
+    assert_equal File.lstat(File.join(@tmpdir, "a.txt")).ino, file.stat.ino unless Gem.win_platform?
+    refute file.directory?
+    assert dir.stat.directory?
+    assert dir.directory?
+  end
+
+  def test_stat_of_a_removed_path_is_nil
+    path = File.join(@tmpdir, "a.txt")
+    File.write(path, "a")
+    @watchcat = Watchcat.watch(@tmpdir, stat: true) { |e| @events << e }
+    sleep 0.2
+    File.delete(path)
+    sleep 0.5
+
+    event = received.find { |e| e.kind.remove? }
+
+    assert_nil event.stat
+    assert_equal [nil], event.stats
+  end
+
+  def test_stat_is_taken_when_the_event_is_delivered
+    @watchcat = Watchcat.watch(@tmpdir, stat: true, atomic_saves: true, filters: { ignore_access: true }) { |e| @events << e }
+    sleep 0.2
+    path = File.join(@tmpdir, "a.txt")
+    # The create is held back by `atomic_saves` while the file is written.
+    File.open(path, "w") do |f|
+      f.write("a")
+      f.flush
+      sleep 0.05
+      f.write("bc")
+    end
+    sleep 0.5
+
+    events = received
+    created = events.find { |e| e.kind.create? && e.paths == [path] }
+
+    refute_nil created
+    assert_equal 3, created.stat.size
+  end
+
+  def test_no_stat_by_default
+    @watchcat = Watchcat.watch(@tmpdir) { |e| @events << e }
+    sleep 0.2
+    File.write(File.join(@tmpdir, "a.txt"), "a")
+    sleep 0.5
+
+    assert_nil @events.pop.stat
+  end
+end
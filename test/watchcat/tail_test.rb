@@ -0,0 +1,95 @@
+# frozen_string_literal: true
+
+require "test_helper"
+require "tmpdir"
+require "fileutils"
+
+class Watchcat::TailTest < Minitest::Test
+  def setup
+    @tmpdir = Dir.mktmpdir("watchcat")
+    @path = File.join(@tmpdir, "app.log")
+    File.write(@path, "old 1\nold 2\n")
+    @lines = Queue.new
+  end
+
+  def teardown
+    @watcher&.stop
+    FileUtils.remove_entry_secure(@tmpdir)
+  end
+
+  def test_yields_appended_lines
+    tail
+    append("new 1\nnew 2\n")
+    append("partial")
+    append(" line\n")
+
+    assert_equal ["new 1", "new 2", "partial line"], received
+  end
+
+  def test_from_start
+    tail(from: :start)
+    append("new 1\n")
+
+    assert_equal ["old 1", "old 2", "new 1"], received
+  end
+
+  def test_from_offset
+    tail(from: 6)
+    append("new 1\n")
+
+    assert_equal ["old 2", "new 1"], received
+  end
+
+  def test_chunks
+    @watcher = Watchcat.tail(@path, lines: false) { |chunk, _event| @lines << chunk }
+    sleep 0.2
+    append("a\nb")
+
+    assert_equal ["a\nb"], received
+  end
+
+  def test_follows_a_rotated_file
+    tail
+    append("last\n")
+    File.rename(@path, "#{@path}.1")
+    File.write(@path, "fresh 1\n")
+    sleep 0.3
+    append("fresh 2\n")
+
+    assert_equal ["last", "fresh 1", "fresh 2"], received
+  end
+
+  def test_follows_a_truncated_file
+    tail
+    File.write(@path, "after truncation\n")
+    sleep 0.3
+
+    assert_equal ["after truncation"], received
+  end
+
+  def test_yields_the_event
+    events = Queue.new
+    @watcher = Watchcat.tail(@path) { |_line, event| events << event }
+    sleep 0.2
+    append("new\n")
+
+    assert_equal [@path], events.pop(timeout: 2).paths
+  end
+
+  private
+
+  def tail(**options)
+    @watcher = Watchcat.tail(@path, **options) { |line, _event| @lines << line }
+    sleep 0.2
+  end
+
+  def append(data)
+    File.open(@path, "a") { |f| f.write(data) }
+    sleep 0.2
+  end
+
+  def received
+    sleep 0.3
+    super(@lines)
+  end
+end
@@ -0,0 +1,114 @@
+# frozen_string_literal: true
+
+require "test_helper"
+require "watchcat/testing"
+
+class Watchcat::TestingTest < Minitest::Test
+  def setup
+    @events = []
+  end
+
+  def teardown
+    @watcher&.stop
+  end
+
+  def test_injected_events_are_delivered_before_inject_returns
+    @watcher = Watchcat.watch("/nonexistent/app", backend: :fake) { |e| @events << e }
+
+    assert Watchcat::Testing.inject(@watcher, kind: :create, paths: "/nonexistent/app/a.txt")
+
+    assert_equal 1, @events.size
+    assert @events[0].kind.create.file?
+    assert_equal ["/nonexistent/app/a.txt"], @events[0].paths
+    assert_equal "a.txt", @events[0].relative_paths.first
+  end
+
+  def test_kinds
+    @watcher = Watchcat.watch("/app", backend: :fake) { |e| @events << e }
+
+    Watchcat::Testing.inject(@watcher, kind: :create_folder, paths: "/app/lib")
+    Watchcat::Testing.inject(@watcher, kind: :modify, paths: "/app/a.rb")
+    Watchcat::Testing.inject(@watcher, kind: :rename, paths: ["/app/a.rb", "/app/b.rb"])
+    Watchcat::Testing.inject(@watcher, kind: :remove, paths: "/app/b.rb")
+
+    assert @events[0].kind.create.folder?
+    assert @events[1].kind.modify.data_change?
+    assert_equal ["/app/a.rb", "/app/b.rb"], [@events[2].src_path, @events[2].dest_path]
+    assert @events[3].kind.remove.file?
+  end
+
+  def test_events_go_through_the_filters
+    @watcher = Watchcat.watch(
+      "/app",
+      backend: :fake,
+      filters: { ignore_remove: true },
+      ignore_hidden: true,
+      patterns: ["*.rb"]
+    ) { |e| @events << e }
+
+    Watchcat::Testing.inject(@watcher, kind: :remove, paths: "/app/a.rb")
+    Watchcat::Testing.inject(@watcher, kind: :modify, paths: "/app/.a.rb")
+    Watchcat::Testing.inject(@watcher, kind: :modify, paths: "/app/a.txt")
+    Watchcat::Testing.inject(@watcher, kind: :modify, paths: "/app/a.rb")
+
+    assert_equal [["/app/a.rb"]], @events.map(&:paths)
+  end
+
+  def test_events_are_debounced
+    @watcher = Watchcat.watch("/app", backend: :fake, debounce: 100) { |e| @events << e }
+
+    3.times { Watchcat::Testing.inject(@watcher, kind: :modify, paths: "/app/a.rb") }
+    sleep 0.3
+
+    assert_equal 1, @events.size
+  end
+
+  def test_watch_does_not_need_the_path_to_exist
+    @watcher = Watchcat.watch("/app", backend: :fake) { |e| @events << e }
+    @watcher.watch("/lib")
+
+    Watchcat::Testing.inject(@watcher, kind: :create, paths: "/lib/a.rb")
+
+    assert_equal 1, @events.size
+  end
+
+  def test_inject_from_the_block
+    @watcher = Watchcat.watch("/app", backend: :fake) do |e|
+      @events << e
+      Watchcat::Testing.inject(@watcher, kind: :remove, paths: e.paths) if e.kind.create?
+    end
+
+    Watchcat::Testing.inject(@watcher, kind: :create, paths: "/app/a.rb")
+    sleep 0.1
+
+    assert_equal 2, @events.size
+  end
+
+  def test_inject_after_stop
+    @watcher = Watchcat.watch("/app", backend: :fake) { |e| @events << e }
+    @watcher.stop
+
+    refute Watchcat::Testing.inject(@watcher, kind: :create, paths: "/app/a.rb")
+    assert_empty @events
+  end
+
+  def test_unknown_kind
+    @watcher = Watchcat.watch("/app", backend: :fake) { |e| @events << e }
+
+    error = assert_raises(ArgumentError) { Watchcat::Testing.inject(@watcher, kind: :write, paths: "/app/a") }
+    assert_match(/unknown kind: write/, error.message)
+    assert_raises(ArgumentError) { Watchcat::Testing.inject(@watcher, kind: :rename, paths: "/app/a") }
+  end
+
+  def test_inject_needs_the_fake_backend
+    @watcher = Watchcat.watch(__dir__) { |e| @events << e }
+
+    assert_raises(ArgumentError) { Watchcat::Testing.inject(@watcher, kind: :create, paths: __FILE__) }
+  end
+
+  def test_unknown_backend
+    assert_raises(ArgumentError) do
+      Watchcat::Watcher.new.watch(["/app"], backend: "inotifyy") { |_| }
+    end
+  end
+end
@@ -0,0 +1,92 @@
+# frozen_string_literal: true
+
+require "test_helper"
+require "tmpdir"
+require "fileutils"
+require "watchcat/testing"
+
+class Watchcat::TrackContentTest < Minitest::Test
+  def setup
+    @tmpdir = Dir.mktmpdir("watchcat")
+    @events = Queue.new
+    File.write(File.join(@tmpdir, "app.css"), "a {}\nb {}\nc {}\n")
+  end
+
+  def teardown
+    @watchcat&.stop
+    FileUtils.remove_entry_secure(@tmpdir)
+  end
+
+  def test_modify_events_carry_a_diff
+    watch(patterns: ["*.css"])
+    File.write(File.join(@tmpdir, "app.css"), "a {}\nB {}\nc {}\nd {}\n")
+    sleep 0.5
+
+    diff = received.filter_map(&:diff).first
+
+    refute_nil diff
+    assert_includes diff.unified, "-b {}\n+B {}\n"
+    assert_includes diff.unified, "+d {}\n"
+    assert_equal [[2...3, 2...3], [4...4, 4...5]], diff.hunks
+  end
+
+  def test_files_not_matching_the_patterns_have_no_diff
+    File.write(File.join(@tmpdir, "notes.txt"), "a\n")
+    watch(patterns: ["*.css"])
+    File.write(File.join(@tmpdir, "notes.txt"), "b\n")
+    sleep 0.5
+
+    assert_empty received.filter_map(&:diff)
+  end
+
+  def test_files_larger_than_max_bytes_have_no_diff
+    watch(patterns: ["*.css"], max_bytes: 10)
+    File.write(File.join(@tmpdir, "app.css"), "a {}\nB {}\nc {}\n")
+    sleep 0.5
+
+    assert_empty received.filter_map(&:diff)
+  end
+
+  def test_an_atomic_save_is_diffed_against_the_replaced_file
+    watch(patterns: ["*.css"])
+    File.write(File.join(@tmpdir, ".app.css.tmp"), "a {}\nb {}\nc {}\nd {}\n")
+    File.rename(File.join(@tmpdir, ".app.css.tmp"), File.join(@tmpdir, "app.css"))
+    sleep 0.5
+
+    diffs = received.filter_map(&:diff)
+
+    assert_equal 1, diffs.size
+    assert_equal [[4...4, 4...5]], diffs[0].hunks
+  end
+
+  def test_changes_filtered_out_are_not_in_the_next_diff
+    path = File.join(@tmpdir, "app.css")
+    @watchcat = Watchcat.watch(@tmpdir, backend: :fake, track_content: { patterns: ["*.css"] }, filters: { ignore_modify: true }) { |e| @events << e }
+    File.write(path, "a {}\nB {}\nc {}\n")
+    Watchcat::Testing.inject(@watchcat, kind: :modify, paths: path)
+    File.write(path, "a {}\nB {}\nc {}\nd {}\n")
+    Watchcat::Testing.inject(@watchcat, kind: :access, paths: path)
+
+    diffs = received.filter_map(&:diff)
+
+    assert_equal 1, diffs.size
+    assert_equal [[4...4, 4...5]], diffs[0].hunks
+  end
+
+  def test_files_changed_the_longest_ago_are_dropped_past_max_total_bytes
+    watch(patterns: ["*.css"], max_total_bytes: 20)
+    File.write(File.join(@tmpdir, "more.css"), "a {}\nb {}\n")
+    sleep 0.2
+    File.write(File.join(@tmpdir, "app.css"), "a {}\nB {}\nc {}\n")
+    sleep 0.5
+
+    assert_empty received.select { |e| e.diff && e.paths == [File.join(@tmpdir, "app.css")] }
+  end
+
+  private
+
+  def watch(**track_content)
+    @watchcat = Watchcat.watch(@tmpdir, track_content: track_content) { |e| @events << e }
+    sleep 0.2
+  end
+end
@@ -0,0 +1,139 @@
+# frozen_string_literal: true
+
+require "test_helper"
+require "tmpdir"
+require "fileutils"
+
+class Watchcat::WaitForTest < Minitest::Test
+  def setup
+    @tmpdir = Dir.mktmpdir("watchcat")
+    @path = File.join(@tmpdir, "a.txt")
+  end
+
+  def teardown
+    @thread&.join
+    FileUtils.remove_entry_secure(@tmpdir)
+  end
+
+  def test_returns_the_create_event
+    later { File.write(@path, "a") }
+
+    event = Watchcat.wait_for(@path, timeout: 2)
+
+    assert event.kind.create?
+    assert_equal "a.txt", File.basename(event.paths.first)
+  end
+
+  def test_returns_right_away_if_the_file_already_exists
+    File.write(@path, "a")
+
+    event = Watchcat.wait_for(@path, timeout: 2)
+
+    assert event.kind.create?
+    assert_equal "existing", event.attributes.info
+  end
+
+  def test_a_file_renamed_into_place_counts_as_created
+    later do
+      File.write(File.join(@tmpdir, "a.tmp"), "a")
+      File.rename(File.join(@tmpdir, "a.tmp"), @path)
+    end
+
+    event = Watchcat.wait_for(@path, timeout: 2)
+
+    assert_includes event.paths.map { |p| File.basename(p) }, "a.txt"
+  end
+
+  def test_modify
+    File.write(@path, "a")
+    later { File.write(@path, "b") }
+
+    event = Watchcat.wait_for(@path, event: :modify, timeout: 2)
+
+    assert event.kind.modify?
+  end
+
+  def test_remove
+    File.write(@path, "a")
+    later { File.delete(@path) }
+
+    event = Watchcat.wait_for(@path, event: :remove, timeout: 2)
+
+    assert_equal "remove", event.kind.event_type
+  end
+
+  def test_remove_returns_right_away_if_the_file_is_missing
+    event = Watchcat.wait_for(@path, event: :remove, timeout: 2)
+
+    assert_equal "missing", event.attributes.info
+  end
+
+  def test_pattern
+    FileUtils.mkdir(File.join(@tmpdir, "logs"))
+    later do
+      File.write(File.join(@tmpdir, "a.txt"), "a")
+      File.write(File.join(@tmpdir, "logs", "b.log"), "b")
+    end
+
+    event = Watchcat.wait_for(@tmpdir, pattern: "*.log", timeout: 2)
+
+    assert_equal "b.log", File.basename(event.paths.first)
+  end
+
+  def test_directory_created_later
+    path = File.join(@tmpdir, "tmp", "pids", "server.pid")
+    later do
+      FileUtils.mkdir_p(File.dirname(path))
+      File.write(path, "1")
+    end
+
+    event = Watchcat.wait_for(path, timeout: 2)
+
+    assert event.kind.create?
+    assert_equal path, event.paths.first
+  end
+
+  def test_pattern_in_a_directory_created_later
+    later do
+      FileUtils.mkdir_p(File.join(@tmpdir, "build", "js"))
+      File.write(File.join(@tmpdir, "build", "js", "app.js"), "a")
+    end
+
+    event = Watchcat.wait_for(File.join(@tmpdir, "build"), pattern: "*.js", timeout: 2)
+
+    assert_equal "app.js", File.basename(event.paths.first)
+  end
+
+  def test_raises_timeout
+    later { File.write(File.join(@tmpdir, "other.txt"), "a") }
+
+    error = assert_raises(Watchcat::Timeout) { Watchcat.wait_for(@path, timeout: 0.5) }
+
+    assert_kind_of Timeout::Error, error
+    assert_match(/no create event for .*a\.txt within 0\.5 seconds/, error.message)
+  end
+
+  def test_unknown_event
+    assert_raises(ArgumentError) { Watchcat.wait_for(@path, event: :rename) }
+  end
+
+  def test_can_be_interrupted
+    thread = Thread.new { Watchcat.wait_for(@path, timeout: nil) }
+    thread.report_on_exception = false
+    sleep 0.2
+
+    thread.raise(RuntimeError, "interrupted")
+
+    assert thread.join(1), "wait_for wasn't interrupted"
+    assert_raises(RuntimeError) { thread.value }
+  end
+
+  private
+
+  def later(&block)
+    @thread = Thread.new do
+      sleep 0.2
+      block.call
+    end
+  end
+end
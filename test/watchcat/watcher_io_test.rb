@@ -0,0 +1,107 @@
+# frozen_string_literal: true
+
+require "test_helper"
+require "tmpdir"
+require "fileutils"
+
+class Watchcat::WatcherIoTest < Minitest::Test
+  def setup
+    skip if windows?
+
+    @tmpdir = Dir.mktmpdir("watchcat")
+    @watcher = Watchcat::Watcher.new
+  end
+
+  def teardown
+    @watcher&.close
+    FileUtils.remove_entry_secure(@tmpdir) if @tmpdir
+  end
+
+  def test_to_io_becomes_readable_when_events_are_pending
+    @watcher.start([@tmpdir], recursive: true)
+    sleep 0.2
+
+    assert_nil IO.select([@watcher], nil, nil, 0)
+
+    File.write(File.join(@tmpdir, "a.txt"), "")
+    readable, = IO.select([@watcher], nil, nil, 2)
+
+    assert_equal [@watcher], readable
+    events = @watcher.drain_events
+    assert_kind_of Watchcat::Event, events.first
+    assert_includes events.map { |e| File.basename(e.paths.first) }, "a.txt"
+  end
+
+  def test_drain_events_does_not_block
+    @watcher.start([@tmpdir], recursive: true)
+
+    assert_equal [], @watcher.drain_events
+    assert_nil IO.select([@watcher.to_io], nil, nil, 0)
+  end
+
+  def test_drain_events_returns_nil_after_close
+    @watcher.start([@tmpdir], recursive: true)
+    @watcher.close
+
+    assert IO.select([@watcher], nil, nil, 2)
+    assert_nil @watcher.drain_events
+  end
+
+  def test_to_io_is_memoized
+    @watcher.start([@tmpdir], recursive: true)
+
+    assert_same @watcher.to_io, @watcher.to_io
+    assert_equal @watcher.fileno, @watcher.to_io.fileno
+  end
+
+  def test_to_io_before_start
+    error = assert_raises(RuntimeError) { @watcher.to_io }
+
+    assert_equal "watcher not started", error.message
+  end
+
+  def test_start_twice
+    @watcher.start([@tmpdir], recursive: true)
+
+    error = assert_raises(RuntimeError) { @watcher.start([@tmpdir], recursive: true) }
+    assert_equal "watcher already started", error.message
+  end
+
+  def test_start_after_a_failed_start
+    assert_raises(ArgumentError) { @watcher.start([File.join(@tmpdir, "missing")], recursive: true) }
+
+    error = assert_raises(RuntimeError) { @watcher.to_io }
+    assert_equal "watcher not started", error.message
+
+    @watcher.start([@tmpdir], recursive: true)
+    assert_equal [], @watcher.drain_events
+  end
+
+  def test_after_fork_starts_a_new_watcher_on_the_same_paths
+    kept = File.join(@tmpdir, "kept")
+    added = File.join(@tmpdir, "added")
+    removed = File.join(@tmpdir, "removed")
+    [kept, added, removed].each { |dir| FileUtils.mkdir_p(dir) }
+    @watcher.start([kept, removed], recursive: false)
+    @watcher.add([added], recursive: false)
+    @watcher.unwatch([removed])
+
+    rebuilt = @watcher.after_fork
+    sleep 0.2
+    [kept, added, removed].each { |dir| File.write(File.join(dir, "a.txt"), "") }
+    sleep 0.3
+
+    paths = rebuilt.drain_events.flat_map(&:paths)
+    assert_includes paths, File.join(kept, "a.txt")
+    assert_includes paths, File.join(added, "a.txt")
+    refute_includes paths, File.join(removed, "a.txt")
+  ensure
+    rebuilt&.close
+  end
+
+  def test_after_fork_before_start
+    error = assert_raises(RuntimeError) { @watcher.after_fork }
+
+    assert_equal "watcher not started", error.message
+  end
+end
@@ -347,6 +347,63 @@ class WatchcatTest < Minitest::Test
     refute_empty symlink_events, "No events detected for symlinked directory operations"
   end
 
+  def test_watch_symlink_directory_with_follow_symlinks_reports_resolved_paths
+    skip if windows?
+
+    outside_dir = Dir.mktmpdir("watchcat")
+    symlink_dir = File.join(@tmpdir, "link_to_dir")
+    File.symlink(outside_dir, symlink_dir)
+
+    events = []
+    @watchcat = Watchcat.watch(@tmpdir, recursive: true, follow_symlinks: true) { |e| events << e }
+
+    sleep 0.2
+    FileUtils.touch(File.join(symlink_dir, "new_file.txt"))
+    sleep 0.3
+
+    event = events.find { |e| e.paths.any? { |path| path.end_with?("new_file.txt") } }
+    refute_nil event, inspect_events(events)
+    assert_equal [File.join(symlink_dir, "new_file.txt")], event.paths
+    assert_equal [File.join(File.realpath(outside_dir), "new_file.txt")], event.resolved_paths
+  ensure
+    FileUtils.remove_entry_secure(outside_dir) if outside_dir
+  end
+
+  def test_watch_symlink_directory_without_follow_symlinks
+    skip unless RUBY_PLATFORM.match?("linux")
+
+    outside_dir = Dir.mktmpdir("watchcat")
+    symlink_dir = File.join(@tmpdir, "link_to_dir")
+    File.symlink(outside_dir, symlink_dir)
+
+    events = []
+    @watchcat = Watchcat.watch(@tmpdir, recursive: true, follow_symlinks: false) { |e| events << e }
+
+    sleep 0.2
+    FileUtils.touch(File.join(symlink_dir, "new_file.txt"))
+    sleep 0.3
+
+    assert_empty events, inspect_events(events)
+  ensure
+    FileUtils.remove_entry_secure(outside_dir) if outside_dir
+  end
+
+  def test_watch_symlink_loop_with_follow_symlinks
+    skip if windows?
+
+    File.symlink(@tmpdir, File.join(@tmpdir, "loop"))
+
+    events = []
+    @watchcat = Watchcat.watch(@tmpdir, recursive: true, follow_symlinks: true) { |e| events << e }
+
+    sleep 0.2
+    FileUtils.touch(File.join(@tmpdir, "a.txt"))
+    sleep 0.3
+
+    assert @watchcat.alive?
+    refute_empty events, inspect_events(events)
+  end
+
   def test_watch_broken_symlink
     skip if windows?
 
@@ -482,6 +539,97 @@ class WatchcatTest < Minitest::Test
     end
   end
 
+  def test_watch_with_ignore_hidden
+    hidden_dir = FileUtils.mkdir(File.join(@tmpdir, ".git")).first
+    events = []
+    @watchcat = Watchcat.watch(@tmpdir, ignore_hidden: true) { |e| events << e }
+
+    sleep 0.2
+    FileUtils.touch(File.join(@tmpdir, ".DS_Store"))
+    FileUtils.touch(File.join(hidden_dir, "index"))
+    FileUtils.touch(File.join(@tmpdir, "a.txt"))
+    sleep 0.3
+
+    refute_empty events, inspect_events(events)
+    events.each do |event|
+      event.paths.each { |path| refute_match(%r{/\.}, path.delete_prefix(@tmpdir)) }
+    end
+  end
+
+  def test_watch_with_ignore_editor_temp
+    events = []
+    @watchcat = Watchcat.watch(@tmpdir, ignore_editor_temp: true) { |e| events << e }
+
+    sleep 0.2
+    %w[.a.txt.swp 4913 .#a.txt #a.txt# a.txt~ a.txt___jb_tmp___].each do |name|
+      FileUtils.touch(File.join(@tmpdir, name))
+    end
+    FileUtils.touch(File.join(@tmpdir, "a.txt"))
+    sleep 0.3
+
+    refute_empty events, inspect_events(events)
+    events.each do |event|
+      event.paths.each { |path| assert_equal "a.txt", File.basename(path), inspect_events(events) }
+    end
+  end
+
+  def test_watch_with_atomic_saves
+    file = File.join(@tmpdir, "a.txt")
+    File.write(file, "old")
+    events = []
+    @watchcat = Watchcat.watch(@tmpdir, atomic_saves: true) { |e| events << e }
+
+    sleep 0.2
+    File.write("#{file}.tmp", "new")
+    File.rename("#{file}.tmp", file)
+    sleep 0.5
+
+    events.each do |event|
+      event.paths.each { |path| assert_equal "a.txt", File.basename(path), inspect_events(events) }
+    end
+    if mac_os? || windows?
+      refute_empty events, inspect_events(events)
+    else
+      assert_equal 1, events.count, inspect_events(events)
+      assert events.first.kind.modify?, inspect_events(events)
+    end
+  end
+
+  def test_watch_with_atomic_saves_and_backup_file
+    file = File.join(@tmpdir, "a.txt")
+    File.write(file, "old")
+    events = []
+    @watchcat = Watchcat.watch(@tmpdir, atomic_saves: true) { |e| events << e }
+
+    sleep 0.2
+    # The way vim saves with `backupcopy=no`.
+    File.rename(file, "#{file}~")
+    File.write(file, "new")
+    File.delete("#{file}~")
+    sleep 0.5
+
+    events.each do |event|
+      event.paths.each { |path| assert_equal "a.txt", File.basename(path), inspect_events(events) }
+    end
+    if mac_os? || windows?
+      refute_empty events, inspect_events(events)
+    else
+      assert_equal 1, events.count, inspect_events(events)
+      assert events.first.kind.modify?, inspect_events(events)
+    end
+  end
+
+  def test_watch_with_atomic_saves_releases_unrelated_events
+    events = []
+    @watchcat = Watchcat.watch(@tmpdir, atomic_saves: true) { |e| events << e }
+
+    sleep 0.2
+    FileUtils.touch(File.join(@tmpdir, "b.txt"))
+    sleep 0.5
+
+    assert(events.any? { |e| e.kind.create? && File.basename(e.paths.first) == "b.txt" }, inspect_events(events))
+  end
+
   def test_a_forked_child_exiting_does_not_stop_the_parents_watcher
     # fork(2) without a following exec() is only async-signal-safe. On macOS the
     # watcher relies on FSEvents/CoreFoundation, which are not fork-safe, so the
@@ -563,4 +711,58 @@ class WatchcatTest < Minitest::Test
 
     refute_equal 0, events.count, inspect_events(events)
   end
+
+  def test_watcher_is_rearmed_in_a_forked_child
+    # See test_a_forked_child_exiting_does_not_stop_the_parents_watcher.
+    skip if windows? || mac_os?
+
+    events = []
+    @watchcat = Watchcat.watch(@tmpdir, recursive: true) { |e| events << e }
+    sleep 0.2
+
+    pid = fork do
+      events.clear
+      sleep 0.2
+      FileUtils.touch(File.join(@tmpdir, "child.txt"))
+      sleep 0.3
+      received = events.any? { |e| File.basename(e.paths.first) == "child.txt" }
+      Process.exit!(received && @watchcat.alive?)
+    end
+    status = nil
+    begin
+      Timeout.timeout(5) { _, status = Process.wait2(pid) }
+    rescue Timeout::Error
+      flunk "forked child did not exit within 5 seconds"
+    end
+    assert status.success?, "child did not receive events: #{status.inspect}"
+
+    events.clear
+    FileUtils.touch(File.join(@tmpdir, "a.txt"))
+    sleep 0.3
+
+    refute_equal 0, events.count, inspect_events(events)
+  end
+
+  def test_after_fork_is_a_no_op_in_the_owner_process
+    @watchcat = Watchcat.watch(@tmpdir, recursive: true) { |_e| }
+    sleep 0.2
+    thread = @watchcat.instance_variable_get(:@watch_thread)
+
+    assert_same @watchcat, @watchcat.after_fork
+    assert_same thread, @watchcat.instance_variable_get(:@watch_thread)
+    assert @watchcat.alive?
+  end
+
+  def test_stopped_watcher_is_not_rearmed_in_a_forked_child
+    skip if windows? || mac_os?
+
+    @watchcat = Watchcat.watch(@tmpdir, recursive: true) { |_e| }
+    sleep 0.2
+    @watchcat.stop
+
+    pid = fork { Process.exit!(!@watchcat.alive?) }
+    _, status = Process.wait2(pid)
+
+    assert status.success?
+  end
 end